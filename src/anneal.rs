@@ -0,0 +1,153 @@
+use crate::krand::*;
+use crate::compat::*;
+use crate::greedy::*;
+use crate::local::*;
+use crate::render::*;
+use crate::tileset::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cooling {
+    // straight line from start_temp to end_temp
+    Linear,
+    // same ratio every step, spends longer at the cold end
+    Exponential,
+}
+
+// every `every` iterations the current map is drawn to {prefix}_{iteration}.png, 0 for never
+#[derive(Clone, Debug)]
+pub struct Snapshots {
+    pub prefix: String,
+    pub every: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnnealParams {
+    pub start_temp: f32,
+    pub end_temp: f32,
+    pub cooling: Cooling,
+    pub iterations: usize,
+    // soft term: how much energy a tile costs for being rarer than the heaviest tile, -ln(weight / max)
+    // each. 0 leaves the mix to the proposals, higher pushes harder towards the weights
+    pub weight_bias: f32,
+    pub snapshots: Option<Snapshots>,
+}
+
+impl Default for AnnealParams {
+    fn default() -> AnnealParams {
+        AnnealParams {
+            start_temp: 1.0,
+            end_temp: 0.05,
+            cooling: Cooling::Linear,
+            iterations: 4_000_000,
+            weight_bias: 0.0,
+            snapshots: None,
+        }
+    }
+}
+
+impl AnnealParams {
+    // t runs from 0 to 1 over the run
+    pub fn temperature(&self, t: f32) -> f32 {
+        match self.cooling {
+            Cooling::Linear => self.start_temp + (self.end_temp - self.start_temp) * t,
+            Cooling::Exponential => self.start_temp * (self.end_temp / self.start_temp).powf(t),
+        }
+    }
+}
+
+// simulated annealing. The energy is the number of mismatched borders plus the soft terms. Each
+// iteration proposes a random tile for a random cell and takes it if the energy doesn't go up, or
+// with odds exp(-increase / temperature) if it does, so early on it can climb out of dead ends
+// that the greedy solver and hill climbing get stuck in. Gives back the lowest energy map it saw
+pub fn anneal_tiling_with(tileset: &TileSet, w: usize, h: usize, seed: u32, params: &AnnealParams, budget: &Budget) -> Vec<TileHandle> {
+    let n_tiles = tileset.vec.len();
+    let all = TileBits::full(n_tiles);
+    let max_weight = tileset.vec.iter().fold(0.0f32, |acc, t| acc.max(t.weight));
+    let tile_cost: Vec<f32> = tileset.vec.iter()
+        .map(|t| params.weight_bias * -(t.weight / max_weight).max(1e-3).ln())
+        .collect();
+
+    let mut tiling = random_fill(tileset, w, h, seed);
+    let mut conflicts = ConflictSet::new(tileset, &tiling, w, h);
+    let mut violations = count_violations(tileset, &tiling, w, h);
+    let mut energy = violations as f32 + tiling.iter().map(|&t| tile_cost[t as usize]).sum::<f32>();
+    let mut best = tiling.clone();
+    let mut best_energy = energy;
+    let mut accepted = 0;
+
+    let mut iteration = 0;
+    let deadline = budget.deadline();
+    while iteration < params.iterations && budget.stop(iteration, deadline).is_none() {
+        if let Some(snapshots) = &params.snapshots {
+            if snapshots.every > 0 && iteration % snapshots.every == 0 {
+                render_tiling(tileset, &tiling, w, h).dump_to_file(&format!("{}_{:08}.png", snapshots.prefix, iteration));
+            }
+        }
+        // nothing left to improve
+        if violations == 0 && params.weight_bias == 0.0 {
+            break;
+        }
+
+        // half the proposals go where the problems are, the rest anywhere so the mix keeps moving
+        let r = khash(seed ^ khash(iteration as u32));
+        let cell = if r & 1 == 0 && !conflicts.is_empty() {
+            conflicts.get(khash(r) as usize % conflicts.len())
+        } else {
+            khash(r) as usize % (w*h)
+        };
+        let old = tiling[cell];
+        let new = tileset.pick_weighted(&all, khash(r ^ 0x9E3779B9)).unwrap();
+        iteration += 1;
+        if new == old {
+            continue;
+        }
+
+        let dv = cell_violations(tileset, &tiling, w, h, cell, new) as i64 - cell_violations(tileset, &tiling, w, h, cell, old) as i64;
+        let de = dv as f32 + tile_cost[new as usize] - tile_cost[old as usize];
+        let temp = params.temperature(iteration as f32 / params.iterations as f32);
+        if de <= 0.0 || uniform_f32(khash(r ^ 0x85EBCA6B)) < (-de / temp).exp() {
+            tiling[cell] = new;
+            conflicts.update_around(tileset, &tiling, w, h, cell);
+            violations = (violations as i64 + dv) as usize;
+            energy += de;
+            accepted += 1;
+            if energy < best_energy {
+                best_energy = energy;
+                best.copy_from_slice(&tiling);
+            }
+        }
+    }
+
+    println!("annealing -- iterations: {} accepted: {} violations: {}", iteration, accepted, count_violations(tileset, &best, w, h));
+    best
+}
+
+#[test]
+fn test_anneal() {
+    let params = AnnealParams { start_temp: 1.0, end_temp: 0.1, cooling: Cooling::Linear, ..AnnealParams::default() };
+    assert_eq!(params.temperature(0.0), 1.0);
+    assert!((params.temperature(0.5) - 0.55).abs() < 1e-6);
+    let exp = AnnealParams { start_temp: 1.0, end_temp: 0.01, cooling: Cooling::Exponential, ..AnnealParams::default() };
+    assert!((exp.temperature(0.5) - 0.1).abs() < 1e-6);
+    assert!((exp.temperature(1.0) - 0.01).abs() < 1e-6);
+
+    let tileset = load_tileset("test_tilesets/roads.png", 3).unwrap();
+    let (w, h) = (12, 10);
+    let prefix = std::env::temp_dir().join("wanggen_anneal").to_str().unwrap().to_string();
+    let params = AnnealParams {
+        iterations: 400000,
+        snapshots: Some(Snapshots { prefix: prefix.clone(), every: 400000 }),
+        ..AnnealParams::default()
+    };
+    let tiling = anneal_tiling_with(&tileset, w, h, 69, &params, &Budget::default());
+    assert_eq!(count_violations(&tileset, &tiling, w, h), 0);
+    let first = format!("{}_{:08}.png", prefix, 0);
+    assert!(std::path::Path::new(&first).exists());
+    std::fs::remove_file(first).unwrap();
+
+    // 0 means no snapshots
+    let never = format!("{}_never", prefix);
+    let params = AnnealParams { iterations: 1000, snapshots: Some(Snapshots { prefix: never.clone(), every: 0 }), ..AnnealParams::default() };
+    anneal_tiling_with(&tileset, w, h, 69, &params, &Budget::default());
+    assert!(!std::path::Path::new(&format!("{}_{:08}.png", never, 0)).exists());
+}
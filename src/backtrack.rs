@@ -0,0 +1,165 @@
+use std::collections::BTreeSet;
+
+use crate::grid::*;
+use crate::krand::*;
+use crate::compat::*;
+use crate::greedy::*;
+use crate::tileset::*;
+
+pub enum SearchOutcome {
+    Solved(Vec<TileHandle>),
+    // searched everything, there's no tiling of that size at all
+    Unsatisfiable,
+    // gave up when the budget ran out, counting placements, with what was placed at the time and
+    // HANDLE_UNSET elsewhere
+    OutOfSteps(Vec<TileHandle>),
+}
+
+// one decision on the trail
+struct Level {
+    cell: usize,
+    // the cell's domain when it was picked, values get crossed off it as they're tried
+    domain_before: TileBits,
+    // what the current value took out of the neighbours' domains
+    pruned: Vec<(usize, TileBits)>,
+    // earlier levels that had a hand in values failing here
+    conflicts: BTreeSet<usize>,
+}
+
+// complete search instead of rolling back squares: forward checking plus conflict-directed
+// backjumping (FC-CBJ). Every placement is kept on a trail and crosses the tiles that can't sit
+// next to it off the neighbours' domains. When a cell runs out of tiles it jumps straight back to
+// the latest decision that actually removed something from it (or from a cell whose wipe-out
+// ruled out its tiles), skipping over everything in between that had nothing to do with it. If it
+// has to jump back past the first decision there's no tiling at all.
+// Fine on small and medium maps, it picks the cell with the fewest options by looking at all of them
+pub fn backtrack_tiling(tileset: &TileSet, w: usize, h: usize, seed: u32, budget: &Budget) -> SearchOutcome {
+    let n_tiles = tileset.vec.len();
+    let mut domains = vec![TileBits::full(n_tiles); w*h];
+    let mut assigned = vec![HANDLE_UNSET; w*h];
+    // levels that pruned each cell, in order
+    let mut pruned_by: Vec<Vec<usize>> = vec![Vec::new(); w*h];
+    let mut levels: Vec<Level> = Vec::new();
+    let mut steps = 0;
+    let deadline = budget.deadline();
+
+    // takes the level's current value back out, leaving it crossed off the cell's domain
+    fn undo_value(level: &mut Level, domains: &mut [TileBits], assigned: &mut [TileHandle], pruned_by: &mut [Vec<usize>], depth: usize) {
+        for (cell, removed) in level.pruned.drain(..) {
+            domains[cell].union(&removed);
+            pruned_by[cell].retain(|&l| l != depth);
+        }
+        domains[level.cell].remove(assigned[level.cell]);
+        assigned[level.cell] = HANDLE_UNSET;
+    }
+
+    loop {
+        // most constrained cell next
+        let next = (0..w*h)
+            .filter(|&cell| assigned[cell] == HANDLE_UNSET)
+            .min_by_key(|&cell| domains[cell].count());
+        let Some(cell) = next else {
+            return SearchOutcome::Solved(assigned);
+        };
+        levels.push(Level {
+            cell,
+            domain_before: domains[cell].clone(),
+            pruned: Vec::new(),
+            conflicts: BTreeSet::new(),
+        });
+
+        // try values at the top of the trail until one sticks
+        loop {
+            let depth = levels.len() - 1;
+            let cell = levels[depth].cell;
+            let choice = tileset.pick_weighted(&domains[cell],
+                khash(seed ^ khash(cell as u32)).wrapping_add(steps as u32));
+
+            if let Some(handle) = choice {
+                if budget.stop(steps, deadline).is_some() {
+                    return SearchOutcome::OutOfSteps(assigned);
+                }
+                steps += 1;
+                assigned[cell] = handle;
+                let mut wiped = None;
+                for (dir, neighbour) in grid_neighbours(w, h, cell) {
+                    if assigned[neighbour] != HANDLE_UNSET {
+                        continue;
+                    }
+                    let mut removed = domains[neighbour].clone();
+                    domains[neighbour].intersect(tileset.index.fits(handle, dir));
+                    removed.subtract(&domains[neighbour]);
+                    if !removed.is_empty() {
+                        levels[depth].pruned.push((neighbour, removed));
+                        pruned_by[neighbour].push(depth);
+                    }
+                    if domains[neighbour].is_empty() {
+                        wiped = Some(neighbour);
+                        break;
+                    }
+                }
+                let Some(wiped) = wiped else {
+                    break;
+                };
+                // whoever emptied that cell besides us is part of why this value can't work
+                let culprits: Vec<usize> = pruned_by[wiped].iter().copied().filter(|&l| l != depth).collect();
+                levels[depth].conflicts.extend(culprits);
+                undo_value(&mut levels[depth], &mut domains, &mut assigned, &mut pruned_by, depth);
+            } else {
+                // out of values, jump back to the latest level to blame
+                let mut conflicts = std::mem::take(&mut levels[depth].conflicts);
+                conflicts.extend(pruned_by[cell].iter().copied());
+                let Some(&target) = conflicts.iter().next_back() else {
+                    return SearchOutcome::Unsatisfiable;
+                };
+                conflicts.remove(&target);
+
+                while levels.len() > target + 1 {
+                    let mut level = levels.pop().unwrap();
+                    let d = levels.len();
+                    if assigned[level.cell] != HANDLE_UNSET {
+                        undo_value(&mut level, &mut domains, &mut assigned, &mut pruned_by, d);
+                    }
+                    domains[level.cell] = level.domain_before;
+                }
+                levels[target].conflicts.extend(conflicts);
+                undo_value(&mut levels[target], &mut domains, &mut assigned, &mut pruned_by, target);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_backtrack_tiling() {
+    use crate::image_output::*;
+    use crate::constraint::*;
+
+    // black on the left, white on the right, so it can never sit next to itself sideways
+    let n = 3;
+    let mut image = ImageBuffer::new(n+1, n+1);
+    for y in 0..n {
+        for x in 0..n {
+            image.set_px(x, y, if x == 0 { (0, 0, 0) } else { (255, 255, 255) });
+        }
+    }
+    image.set_px(0, n, (1, 0, 0));
+    let tileset = make_tileset(&image, n, None).unwrap();
+    assert!(matches!(backtrack_tiling(&tileset, 1, 5, 69, &Budget::default().with_steps(1000)), SearchOutcome::Solved(_)));
+    assert!(matches!(backtrack_tiling(&tileset, 2, 2, 69, &Budget::default().with_steps(1000)), SearchOutcome::Unsatisfiable));
+
+    let tileset = load_tileset("test_tilesets/testvillage.png", 3).unwrap();
+    let (w, h) = (12, 12);
+    let tiling = match backtrack_tiling(&tileset, w, h, 69, &Budget::default().with_steps(100000)) {
+        SearchOutcome::Solved(tiling) => tiling,
+        _ => panic!("testvillage should tile"),
+    };
+    for j in 0..h {
+        for i in 0..w {
+            let a = tileset.vec[tiling[j*w + i] as usize].constraint;
+            assert!(i + 1 == w || tile_constraint_fits(a, tileset.vec[tiling[j*w + i + 1] as usize].constraint, Dir::East));
+            assert!(j + 1 == h || tile_constraint_fits(a, tileset.vec[tiling[(j+1)*w + i] as usize].constraint, Dir::South));
+        }
+    }
+
+    assert!(matches!(backtrack_tiling(&tileset, w, h, 69, &Budget::default().with_steps(10)), SearchOutcome::OutOfSteps(_)));
+}
@@ -0,0 +1,101 @@
+use palette::pixel::Srgb;
+use palette::{Lab, Rgb};
+use serde::Deserialize;
+
+// "#37e027" in a manifest
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct HexColour(pub (u8, u8, u8));
+
+impl TryFrom<String> for HexColour {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<HexColour, String> {
+        let hex = s.strip_prefix('#').unwrap_or(&s);
+        let channel = |i: usize| hex.get(i..i+2).and_then(|c| u8::from_str_radix(c, 16).ok());
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(HexColour((r, g, b))),
+            _ => Err(format!("{} isn't a #rrggbb colour", s)),
+        }
+    }
+}
+
+// L* on the usual 0..100 scale, palette squashes it to 0..1 and a*, b* by 128
+fn lab(px: (u8, u8, u8)) -> (f32, f32, f32) {
+    let rgb: Rgb = Srgb::new_u8(px.0, px.1, px.2).to_linear().into();
+    let lab = Lab::from(rgb);
+    (lab.l * 100.0, lab.a * 128.0, lab.b * 128.0)
+}
+
+// CIE76, plain distance in Lab. Around 2.3 is the smallest difference anyone notices
+pub fn delta_e(c1: (u8, u8, u8), c2: (u8, u8, u8)) -> f32 {
+    let (l1, a1, b1) = lab(c1);
+    let (l2, a2, b2) = lab(c2);
+    ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+}
+
+// which colours count as the same for border matching. Colours in a declared class are merged,
+// then each colour in turn joins the first group whose representative is within tolerance of it.
+// It's measured against the representative and not any member, so a gradient of small steps
+// doesn't chain into one colour. Gives back a representative for each of colours, the first one
+// of its group, so the answer doesn't depend on anything but the order they're passed in
+pub fn colour_representatives(colours: &[(u8, u8, u8)], classes: &[Vec<(u8, u8, u8)>], tolerance: f32) -> Vec<(u8, u8, u8)> {
+    let mut parent: Vec<usize> = (0..colours.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    fn union(parent: &mut [usize], a: usize, b: usize) {
+        let (ra, rb) = (root(parent, a), root(parent, b));
+        // lower index wins so the representative is the first seen
+        parent[ra.max(rb)] = ra.min(rb);
+    }
+
+    for class in classes {
+        let members: Vec<usize> = (0..colours.len()).filter(|&i| class.contains(&colours[i])).collect();
+        for pair in members.windows(2) {
+            union(&mut parent, pair[0], pair[1]);
+        }
+    }
+    if tolerance > 0.0 {
+        for b in 0..colours.len() {
+            // already in an earlier group by a class
+            if root(&mut parent, b) != b {
+                continue;
+            }
+            let near = (0..b).find(|&a| root(&mut parent, a) == a && delta_e(colours[a], colours[b]) <= tolerance);
+            if let Some(a) = near {
+                union(&mut parent, a, b);
+            }
+        }
+    }
+
+    (0..colours.len()).map(|i| colours[root(&mut parent, i)]).collect()
+}
+
+#[test]
+fn test_colour_representatives() {
+    assert_eq!(HexColour::try_from("#37e027".to_string()), Ok(HexColour((0x37, 0xe0, 0x27))));
+    assert!(HexColour::try_from("#37e02".to_string()).is_err());
+    assert!(HexColour::try_from("#37e0zz".to_string()).is_err());
+
+    let g1 = (0x37, 0xe0, 0x27);
+    let g2 = (0x38, 0xe0, 0x27);
+    let blue = (0, 0, 255);
+    let brown = (128, 64, 0);
+    assert!(delta_e(g1, g2) < 1.0);
+    assert!(delta_e(g1, blue) > 50.0);
+
+    let colours = [g1, blue, g2, brown];
+    assert_eq!(colour_representatives(&colours, &[], 0.0), colours);
+    assert_eq!(colour_representatives(&colours, &[], 2.3), [g1, blue, g1, brown]);
+    assert_eq!(colour_representatives(&colours, &[vec![brown, blue]], 0.0), [g1, blue, g2, blue]);
+
+    // a chain of greys each a bit over 2 from the last, a and c are further apart than that
+    let (a, b, c) = ((100, 100, 100), (103, 103, 103), (106, 106, 106));
+    assert!(delta_e(a, b) <= 2.3 && delta_e(b, c) <= 2.3 && delta_e(a, c) > 2.3);
+    assert_eq!(colour_representatives(&[a, b, c], &[], 2.3), [a, a, c]);
+}
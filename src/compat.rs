@@ -0,0 +1,175 @@
+use crate::grid::*;
+use crate::constraint::*;
+use crate::tileset::*;
+
+// a set of tile handles, one bit each
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileBits {
+    words: Vec<u64>,
+}
+
+impl TileBits {
+    pub fn empty(n_tiles: usize) -> TileBits {
+        TileBits { words: vec![0; n_tiles.div_ceil(64)] }
+    }
+
+    pub fn full(n_tiles: usize) -> TileBits {
+        let mut bits = TileBits::empty(n_tiles);
+        for handle in 0..n_tiles {
+            bits.insert(handle as TileHandle);
+        }
+        bits
+    }
+
+    pub fn insert(&mut self, handle: TileHandle) {
+        self.words[handle as usize / 64] |= 1 << (handle % 64);
+    }
+
+    pub fn remove(&mut self, handle: TileHandle) {
+        self.words[handle as usize / 64] &= !(1 << (handle % 64));
+    }
+
+    pub fn contains(&self, handle: TileHandle) -> bool {
+        self.words[handle as usize / 64] & (1 << (handle % 64)) != 0
+    }
+
+    pub fn intersect(&mut self, other: &TileBits) {
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= b;
+        }
+    }
+
+    pub fn union(&mut self, other: &TileBits) {
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a |= b;
+        }
+    }
+
+    // everything in self that isn't in other
+    pub fn subtract(&mut self, other: &TileBits) {
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= !b;
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    // lowest handle first
+    pub fn iter(&self) -> impl Iterator<Item = TileHandle> + '_ {
+        self.words.iter().enumerate().flat_map(|(wi, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros();
+                word &= word - 1;
+                Some(wi as TileHandle * 64 + bit)
+            })
+        })
+    }
+}
+
+// which tiles can sit next to which, worked out once so placing a tile is a few ANDs instead of
+// a constraint_match against every tile. fits[dir.idx()][a] is every tile allowed in direction
+// dir of a, taking the rules into account as well as the borders
+pub struct CompatIndex {
+    pub n_tiles: usize,
+    fits: [Vec<TileBits>; 4],
+}
+
+impl CompatIndex {
+    pub fn empty() -> CompatIndex {
+        CompatIndex { n_tiles: 0, fits: [Vec::new(), Vec::new(), Vec::new(), Vec::new()] }
+    }
+
+    pub fn new(tileset: &TileSet) -> CompatIndex {
+        let n_tiles = tileset.vec.len();
+        let fits = DIRS.map(|dir| {
+            (0..n_tiles).map(|a| {
+                let mut bits = TileBits::empty(n_tiles);
+                for b in 0..n_tiles {
+                    let (a, b) = (a as TileHandle, b as TileHandle);
+                    let borders_fit = || tile_constraint_fits(tileset.vec[a as usize].constraint, tileset.vec[b as usize].constraint, dir);
+                    let ok = match &tileset.rules {
+                        Some(rules) => rules.pair_ok(a, b, dir, borders_fit),
+                        None => borders_fit(),
+                    };
+                    if ok {
+                        bits.insert(b);
+                    }
+                }
+                bits
+            }).collect()
+        });
+        CompatIndex { n_tiles, fits }
+    }
+
+    pub fn fits(&self, a: TileHandle, dir: Dir) -> &TileBits {
+        &self.fits[dir.idx()][a as usize]
+    }
+
+    // everything allowed in direction dir of at least one of the tiles in domain
+    pub fn support(&self, domain: &TileBits, dir: Dir) -> TileBits {
+        let mut bits = TileBits::empty(self.n_tiles);
+        for a in domain.iter() {
+            bits.union(self.fits(a, dir));
+        }
+        bits
+    }
+
+    // everything that can go in a cell with these neighbours (indexed by Dir::idx), unset and
+    // failed neighbours don't restrict anything
+    pub fn candidates(&self, neighbours: &[TileHandle; 4]) -> TileBits {
+        let mut bits = TileBits::full(self.n_tiles);
+        for dir in DIRS {
+            let neighbour = neighbours[dir.idx()];
+            if neighbour != HANDLE_UNSET && neighbour != HANDLE_FAILED_PLACEMENT {
+                // we sit in direction dir.opposite() of the neighbour
+                bits.intersect(self.fits(neighbour, dir.opposite()));
+            }
+        }
+        bits
+    }
+}
+
+#[test]
+fn test_compat_index() {
+    let mut bits = TileBits::empty(130);
+    for h in [0, 63, 64, 129] {
+        bits.insert(h);
+    }
+    assert_eq!(bits.iter().collect::<Vec<_>>(), [0, 63, 64, 129]);
+    assert_eq!(bits.count(), 4);
+    let mut full = TileBits::full(130);
+    assert_eq!(full.count(), 130);
+    full.intersect(&bits);
+    assert_eq!(full, bits);
+    assert!(!full.contains(1) && full.contains(129));
+
+    // the index has to agree with asking the constraints directly
+    let tileset = load_tileset("test_tilesets/testvillage.png", 3).unwrap();
+    let n_tiles = tileset.vec.len() as TileHandle;
+    for dir in DIRS {
+        for a in 0..n_tiles {
+            for b in 0..n_tiles {
+                let fits = tile_constraint_fits(tileset.vec[a as usize].constraint, tileset.vec[b as usize].constraint, dir);
+                assert_eq!(tileset.index.fits(a, dir).contains(b), fits);
+            }
+        }
+    }
+    let neighbours = [0, HANDLE_UNSET, 1, HANDLE_FAILED_PLACEMENT];
+    let candidates = tileset.index.candidates(&neighbours);
+    for b in 0..n_tiles {
+        let c = tileset.vec[b as usize].constraint;
+        let fits = tile_constraint_fits(tileset.vec[0].constraint, c, Dir::South)
+            && tile_constraint_fits(tileset.vec[1].constraint, c, Dir::North);
+        assert_eq!(candidates.contains(b), fits);
+    }
+}
@@ -1,162 +1,376 @@
-use assert_hex::*;
 use crate::grid::*;
 
+// biggest tile side length we can pack, one mask bit per pixel in a u64
+pub const MAX_TILE_SIZE: usize = 8;
+pub const MAX_TILE_PX: usize = MAX_TILE_SIZE * MAX_TILE_SIZE;
+
+// an n x n tile worth of pixels, row major. Only the border ring is ever constrained,
+// a set mask bit means don't care about that pixel
 #[derive(Copy, Clone)]
 pub struct Constraint {
-    pub r: u64,
-    pub g: u64,
-    pub b: u64,
+    pub n: usize,
+    pub px: [(u8, u8, u8); MAX_TILE_PX],
     pub mask: u64,
 }
 
+fn px_bit(n: usize, x: usize, y: usize) -> u64 {
+    1 << (y*n + x)
+}
+
+// bits that actually correspond to a pixel of an n x n tile
+fn valid_mask(n: usize) -> u64 {
+    if n*n == 64 {
+        0xFFFFFFFFFFFFFFFF
+    } else {
+        (1 << (n*n)) - 1
+    }
+}
+
+fn row_mask(n: usize, y: usize) -> u64 {
+    (0..n).fold(0, |acc, x| acc | px_bit(n, x, y))
+}
+
+fn col_mask(n: usize, x: usize) -> u64 {
+    (0..n).fold(0, |acc, y| acc | px_bit(n, x, y))
+}
+
+pub fn border_mask(n: usize) -> u64 {
+    row_mask(n, 0) | row_mask(n, n-1) | col_mask(n, 0) | col_mask(n, n-1)
+}
+
+pub fn constraint_unconstrained(n: usize) -> Constraint {
+    Constraint {
+        n,
+        px: [(0, 0, 0); MAX_TILE_PX],
+        mask: 0xFFFFFFFFFFFFFFFF,
+    }
+}
+
 pub fn constraint_match(c1: Constraint, c2: Constraint) -> bool {
-    let mask = c1.mask | c2.mask;
-    c1.r | mask == c2.r | mask &&
-    c1.g | mask == c2.g | mask &&
-    c1.b | mask == c2.b | mask
+    let mut care = !(c1.mask | c2.mask) & valid_mask(c1.n);
+    while care != 0 {
+        let i = care.trailing_zeros() as usize;
+        if c1.px[i] != c2.px[i] {
+            return false;
+        }
+        care &= care - 1;
+    }
+    true
 }
 
 #[test]
 pub fn test_constraint_match() {
-    let unconstrained_all = Constraint {
-        r: 0,
-        g: 0,
-        b: 0,
-        mask: 0xFFFFFFFFFFFFFFFF,
-    };
-    let unconstrained_bottom = Constraint {
-        r: 0,
-        g: 0,
-        b: 0,
-        mask: 0x0000000000FFFFFF,
-    };
-    let red_bottom = Constraint {
-        r: 0x0000000000FFFFFF,
-        g: 0,
-        b: 0,
-        mask: 0xFFFFFFFFFF000000,
-    };
-    let red_all = Constraint {
-        r: 0xFFFFFFFFFFFFFFFF,
-        g: 0,
-        b: 0,
-        mask: 0,
-    };
-    let magenta_bottom = Constraint {
-        r: 0x0000000000FFFFFF,
-        g: 0,
-        b: 0x0000000000FFFFFF,
-        mask: 0xFFFFFFFFFF000000,
-    };
-    assert_eq!(constraint_match(unconstrained_all, red_bottom), true);
-    assert_eq!(constraint_match(unconstrained_all, red_all), true);
-    assert_eq!(constraint_match(unconstrained_all, magenta_bottom), true);
-    assert_eq!(constraint_match(unconstrained_bottom, magenta_bottom), true);
-    assert_eq!(constraint_match(red_bottom, red_all), true);
-    assert_eq!(constraint_match(red_bottom, magenta_bottom), false);
+    let n = 3;
+    let red = (255, 0, 0);
+    let magenta = (255, 0, 255);
+
+    let unconstrained_all = constraint_unconstrained(n);
+    let mut unconstrained_bottom = constraint_unconstrained(n);
+    unconstrained_bottom.mask = row_mask(n, 2);
+    let mut red_bottom = constraint_unconstrained(n);
+    red_bottom.mask = !row_mask(n, 2);
+    let mut red_all = constraint_from_px_colour(n, &[red; 9]);
+    red_all.mask = 0;
+    let mut magenta_bottom = constraint_unconstrained(n);
+    magenta_bottom.mask = !row_mask(n, 2);
+    for x in 0..n {
+        red_bottom.px[6 + x] = red;
+        magenta_bottom.px[6 + x] = magenta;
+    }
 
+    assert!(constraint_match(unconstrained_all, red_bottom));
+    assert!(constraint_match(unconstrained_all, red_all));
+    assert!(constraint_match(unconstrained_all, magenta_bottom));
+    assert!(constraint_match(unconstrained_bottom, magenta_bottom));
+    assert!(constraint_match(red_bottom, red_all));
+    assert!(!constraint_match(red_bottom, magenta_bottom));
 }
 
-fn flip_ud(x: u64) -> u64 {
-    // swap first 3 bytes with last 3 bytes
-    (x & 0x000000FFFF000000) | (x >> 40) | (x << 40)
+// reverse the rows
+fn flip_ud(c: Constraint) -> Constraint {
+    let n = c.n;
+    let mut out = c;
+    out.mask = !valid_mask(n);
+    for y in 0..n {
+        for x in 0..n {
+            out.px[(n-1-y)*n + x] = c.px[y*n + x];
+            if c.mask & px_bit(n, x, y) != 0 {
+                out.mask |= px_bit(n, x, n-1-y);
+            }
+        }
+    }
+    out
 }
 
 #[test]
 fn test_flip_ud() {
-    assert_eq_hex!(flip_ud(0xF000000000000000), 0x0000000000F00000);
-    assert_eq_hex!(flip_ud(0x0000000000F00000), 0xF000000000000000);
-    assert_eq_hex!(flip_ud(0x00000F0000000000), 0x000000000000000F);
-    assert_eq_hex!(flip_ud(0x000000000000000F), 0x00000F0000000000);
-    assert_eq_hex!(flip_ud(0xFFF0000000F00000), 0xF000000000FFF000);
+    for n in 2..=MAX_TILE_SIZE {
+        let mut c = constraint_unconstrained(n);
+        c.mask = !row_mask(n, 0);
+        c.px[0] = (1, 2, 3);
+        c.px[n-1] = (4, 5, 6);
+        let f = flip_ud(c);
+        assert_eq!(f.mask & valid_mask(n), !row_mask(n, n-1) & valid_mask(n));
+        assert_eq!(f.px[(n-1)*n], (1, 2, 3));
+        assert_eq!(f.px[n*n-1], (4, 5, 6));
+        assert_eq!(flip_ud(f).px[0], (1, 2, 3));
+    }
 }
 
-fn flip_lr(x: u64) -> u64 {
-    // swap left and right bytes
-     x & 0x00FF00000000FF00 |
-    (x & 0xFF00000000FF0000) >> 16 |
-    (x & 0x0000FF00000000FF) << 16 |
-    (x & 0x00000000FF000000) << 8  |
-    (x & 0x000000FF00000000) >> 8
+// reverse the columns
+fn flip_lr(c: Constraint) -> Constraint {
+    let n = c.n;
+    let mut out = c;
+    out.mask = !valid_mask(n);
+    for y in 0..n {
+        for x in 0..n {
+            out.px[y*n + (n-1-x)] = c.px[y*n + x];
+            if c.mask & px_bit(n, x, y) != 0 {
+                out.mask |= px_bit(n, n-1-x, y);
+            }
+        }
+    }
+    out
 }
 
-fn constraint_flip_ud(c: Constraint) -> Constraint {
-    Constraint {
-        r: flip_ud(c.r),
-        g: flip_ud(c.g),
-        b: flip_ud(c.b),
-        mask: flip_ud(c.mask),
+#[test]
+fn test_flip_lr() {
+    for n in 2..=MAX_TILE_SIZE {
+        let mut c = constraint_unconstrained(n);
+        c.mask = !col_mask(n, 0);
+        c.px[0] = (1, 2, 3);
+        let f = flip_lr(c);
+        assert_eq!(f.mask & valid_mask(n), !col_mask(n, n-1) & valid_mask(n));
+        assert_eq!(f.px[n-1], (1, 2, 3));
     }
 }
 
-fn constraint_flip_lr(c: Constraint) -> Constraint {
-    Constraint {
-        r: flip_lr(c.r),
-        g: flip_lr(c.g),
-        b: flip_lr(c.b),
-        mask: flip_lr(c.mask),
+// the side of the target that faces a source tile lying in direction dir.opposite()
+fn dir_mask(n: usize, dir: Dir) -> u64 {
+    match dir {
+        Dir::North => row_mask(n, n-1),
+        Dir::South => row_mask(n, 0),
+        Dir::East => col_mask(n, 0),
+        Dir::West => col_mask(n, n-1),
     }
 }
 
-const DIR_MASK_SOUTH: u64 = 0xFFFFFF0000000000;
-const DIR_MASK_NORTH: u64 = 0x0000000000FFFFFF;
-const DIR_MASK_EAST: u64  = 0xFF0000FF00FF0000;
-const DIR_MASK_WEST: u64  = 0x0000FF00FF0000FF;
-
+// target is the neighbour of source in direction dir
 pub fn constraint_add(target: &mut Constraint, source: Constraint, dir: Dir) {
-
-    let (dir_mask, flipped_source) = match dir {
-        Dir::North => (DIR_MASK_NORTH, constraint_flip_ud(source)),
-        Dir::South => (DIR_MASK_SOUTH, constraint_flip_ud(source)),
-        Dir::East => (DIR_MASK_EAST, constraint_flip_lr(source)),
-        Dir::West => (DIR_MASK_WEST, constraint_flip_lr(source)),
+    let n = target.n;
+    let dir_mask = dir_mask(n, dir);
+    let flipped_source = match dir {
+        Dir::North | Dir::South => flip_ud(source),
+        Dir::East | Dir::West => flip_lr(source),
     };
 
     target.mask &= !dir_mask;                        // zero the mask bits
     target.mask |= dir_mask & flipped_source.mask;   // add relevant source bits
 
-    target.r &= !dir_mask;
-    target.r |= dir_mask &flipped_source.r;
+    let mut bits = dir_mask;
+    while bits != 0 {
+        let i = bits.trailing_zeros() as usize;
+        target.px[i] = flipped_source.px[i];
+        bits &= bits - 1;
+    }
+}
+
+#[test]
+fn test_constraint_add() {
+    for n in 2..=MAX_TILE_SIZE {
+        let mut px = vec![(0, 0, 0); n*n];
+        for (x, p) in px.iter_mut().take(n).enumerate() {
+            *p = (x as u8, 1, 1); // top row
+        }
+        let source = constraint_from_px_colour(n, &px);
+        let mut target = constraint_unconstrained(n);
+        constraint_add(&mut target, source, Dir::North);
+        assert_eq!(target.mask & valid_mask(n), !row_mask(n, n-1) & valid_mask(n));
+        for x in 0..n {
+            assert_eq!(target.px[(n-1)*n + x], (x as u8, 1, 1));
+        }
+        // a tile fits north of source if its bottom row is source's top row
+        assert!(!constraint_match(target, source));
+        let mut above = px.clone();
+        for x in 0..n {
+            above[(n-1)*n + x] = (x as u8, 1, 1);
+        }
+        assert!(constraint_match(target, constraint_from_px_colour(n, &above)));
+    }
+}
 
-    target.g &= !dir_mask;
-    target.g |= dir_mask &flipped_source.g;
+// border pixels painted this match anything, so are fully transparent ones (see make_tileset)
+pub const WILDCARD: (u8, u8, u8) = (255, 0, 255);
 
-    target.b &= !dir_mask;
-    target.b |= dir_mask &flipped_source.b;
+// px_colour is n x n row major, the interior doesn't take part in constraints
+pub fn constraint_from_px_colour(n: usize, px_colour: &[(u8, u8, u8)]) -> Constraint {
+    let mut c = Constraint {
+        n,
+        px: [(0, 0, 0); MAX_TILE_PX],
+        mask: !border_mask(n),
+    };
+    c.px[..n*n].copy_from_slice(&px_colour[..n*n]);
+    for (i, &px) in px_colour[..n*n].iter().enumerate() {
+        if px == WILDCARD {
+            c.mask |= 1 << i;
+        }
+    }
+    c
 }
 
-pub fn constraint_from_px_colour(px_colour: [(u8, u8, u8); 9]) -> Constraint {
-    Constraint {
-        r: 
-            (px_colour[0].0 as u64) << 56 |
-            (px_colour[1].0 as u64) << 48 |
-            (px_colour[2].0 as u64) << 40 |
-            (px_colour[3].0 as u64) << 32 |
-            // skip middle one for constraints
-            (px_colour[5].0 as u64) << 24 |
-            (px_colour[6].0 as u64) << 16 |
-            (px_colour[7].0 as u64) << 8 |
-            (px_colour[8].0 as u64) << 0,
-        g: 
-            (px_colour[0].1 as u64) << 56 |
-            (px_colour[1].1 as u64) << 48 |
-            (px_colour[2].1 as u64) << 40 |
-            (px_colour[3].1 as u64) << 32 |
-            // skip middle one for constraints
-            (px_colour[5].1 as u64) << 24 |
-            (px_colour[6].1 as u64) << 16 |
-            (px_colour[7].1 as u64) << 8 |
-            (px_colour[8].1 as u64) << 0,
-        b: 
-            (px_colour[0].2 as u64) << 56 |
-            (px_colour[1].2 as u64) << 48 |
-            (px_colour[2].2 as u64) << 40 |
-            (px_colour[3].2 as u64) << 32 |
-            // skip middle one for constraints
-            (px_colour[5].2 as u64) << 24 |
-            (px_colour[6].2 as u64) << 16 |
-            (px_colour[7].2 as u64) << 8 |
-            (px_colour[8].2 as u64) << 0,
-        mask: 0,
-    }
-}
\ No newline at end of file
+// wildcards still have to be drawn as something, take the closest pixel that isn't one.
+// The renderer prefers whatever the neighbour put there, this is for when it can't
+pub fn fill_wildcards(n: usize, px_colour: &mut [(u8, u8, u8)]) {
+    let original = px_colour[..n*n].to_vec();
+    for (i, px) in px_colour[..n*n].iter_mut().enumerate() {
+        if *px != WILDCARD {
+            continue;
+        }
+        let (x, y) = (i % n, i / n);
+        *px = original.iter().enumerate()
+            .filter(|(_, &other)| other != WILDCARD)
+            .min_by_key(|(k, _)| (k % n).abs_diff(x) + (k / n).abs_diff(y))
+            .map(|(_, &other)| other)
+            .unwrap_or((128, 128, 128));
+    }
+}
+
+// the pixels along one side of the tile, going left to right or top to bottom
+pub fn constraint_side(c: Constraint, dir: Dir) -> Vec<(u8, u8, u8)> {
+    let n = c.n;
+    (0..n).map(|k| match dir {
+        Dir::North => c.px[k],
+        Dir::South => c.px[(n-1)*n + k],
+        Dir::West => c.px[k*n],
+        Dir::East => c.px[k*n + n-1],
+    }).collect()
+}
+
+// could b go in direction dir of a
+pub fn constraint_fits(a: Constraint, b: Constraint, dir: Dir) -> bool {
+    let mut target = constraint_unconstrained(a.n);
+    constraint_add(&mut target, a, dir);
+    constraint_match(target, b)
+}
+
+// the other backend: each side of a tile carries a named socket and sides meet if their sockets pair up.
+// sockets are read clockwise around the tile, so where two tiles meet the sides run opposite ways and an
+// asymmetric socket x pairs with its mirror image x' rather than itself. symmetric ones pair with themselves
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Socket {
+    pub id: u16,
+    pub flipped: bool,
+    pub symmetric: bool,
+}
+
+pub fn socket_pairs(a: Socket, b: Socket) -> bool {
+    a.id == b.id && (a.symmetric || a.flipped != b.flipped)
+}
+
+// indexed by Dir::idx. For a tile its own sockets, for a cell the sockets of the neighbours facing it
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SocketConstraint {
+    pub sides: [Option<Socket>; 4],
+}
+
+pub fn socket_constraint_match(c1: SocketConstraint, c2: SocketConstraint) -> bool {
+    c1.sides.iter().zip(c2.sides.iter()).all(|(s1, s2)| match (s1, s2) {
+        (Some(s1), Some(s2)) => socket_pairs(*s1, *s2),
+        _ => true,
+    })
+}
+
+// target is the neighbour of source in direction dir
+pub fn socket_constraint_add(target: &mut SocketConstraint, source: SocketConstraint, dir: Dir) {
+    target.sides[dir.opposite().idx()] = source.sides[dir.idx()];
+}
+
+// mirror left to right then rotate clockwise rot times, same as the pixels get. A symmetric socket
+// looks the same mirrored, so it stays unflipped and the copy can be spotted as a duplicate
+pub fn socket_constraint_transform(c: SocketConstraint, mirror: bool, rot: usize) -> SocketConstraint {
+    let mut sides = c.sides;
+    if mirror {
+        sides.swap(Dir::East.idx(), Dir::West.idx());
+        for s in sides.iter_mut().flatten() {
+            s.flipped = !s.flipped && !s.symmetric;
+        }
+    }
+    for _ in 0..rot {
+        sides = [sides[3], sides[0], sides[1], sides[2]];
+    }
+    SocketConstraint { sides }
+}
+
+#[test]
+fn test_wildcards() {
+    let n = 3;
+    let g = (0, 255, 0);
+    let b = (0, 0, 255);
+    // grass with a wildcard middle on the left side
+    let mut px = [g; 9];
+    px[3] = WILDCARD;
+    let c = constraint_from_px_colour(n, &px);
+    assert_eq!(c.mask & border_mask(n), px_bit(n, 0, 1));
+
+    // the wildcard takes anything, but the rest of the side still has to fit
+    let mut water = [b; 9];
+    assert!(!constraint_fits(constraint_from_px_colour(n, &water), c, Dir::East));
+    water[3] = WILDCARD;
+    water[5] = WILDCARD;
+    assert!(!constraint_fits(constraint_from_px_colour(n, &water), c, Dir::East));
+    let mut mixed = [b; 9];
+    for y in 0..n {
+        mixed[y*n + 2] = if y == 1 { b } else { g };
+    }
+    assert!(constraint_fits(constraint_from_px_colour(n, &mixed), c, Dir::East));
+
+    fill_wildcards(n, &mut px);
+    assert_eq!(px, [g; 9]);
+    let mut all = [WILDCARD; 9];
+    fill_wildcards(n, &mut all);
+    assert_eq!(all[0], (128, 128, 128));
+}
+
+#[test]
+fn test_sockets() {
+    let sym = Socket { id: 0, flipped: false, symmetric: true };
+    let road = Socket { id: 1, flipped: false, symmetric: false };
+    let road_f = Socket { id: 1, flipped: true, symmetric: false };
+    assert!(socket_pairs(sym, sym));
+    assert!(!socket_pairs(sym, road));
+    assert!(!socket_pairs(road, road));
+    assert!(socket_pairs(road, road_f));
+
+    let tile = SocketConstraint { sides: [Some(sym), Some(road), Some(sym), Some(sym)] };
+    let mut cell = SocketConstraint { sides: [None; 4] };
+    // tile to the west of the cell with road on its east side
+    socket_constraint_add(&mut cell, tile, Dir::East);
+    assert_eq!(cell.sides[Dir::West.idx()], Some(road));
+    assert!(!socket_constraint_match(cell, tile));
+    let mirrored = socket_constraint_transform(tile, true, 0);
+    assert_eq!(mirrored.sides[Dir::West.idx()], Some(road_f));
+    assert!(socket_constraint_match(cell, mirrored));
+    // rotating clockwise takes east round to south
+    let rotated = socket_constraint_transform(tile, false, 1);
+    assert_eq!(rotated.sides[Dir::South.idx()], Some(road));
+}
+
+// which backend a tileset uses, picked per tileset
+#[derive(Copy, Clone)]
+pub enum TileConstraint {
+    Pixels(Constraint),
+    Sockets(SocketConstraint),
+}
+
+// could b go in direction dir of a
+pub fn tile_constraint_fits(a: TileConstraint, b: TileConstraint, dir: Dir) -> bool {
+    match (a, b) {
+        (TileConstraint::Pixels(a), TileConstraint::Pixels(b)) => constraint_fits(a, b, dir),
+        (TileConstraint::Sockets(a), TileConstraint::Sockets(b)) => {
+            let mut target = SocketConstraint { sides: [None; 4] };
+            socket_constraint_add(&mut target, a, dir);
+            socket_constraint_match(target, b)
+        },
+        _ => panic!("mixed constraint backends"),
+    }
+}
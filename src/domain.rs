@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use crate::grid::*;
+use crate::compat::*;
+use crate::tileset::*;
+
+// what could still go in each cell. Placing a tile shrinks its cell down to that one tile and
+// propagate then knocks out everything that can't sit next to what's left in the neighbours, and
+// what can't sit next to those, and so on (AC-3). A cell running out of options shows up straight
+// away instead of whenever the solver gets round to it
+pub struct Domains {
+    pub w: usize,
+    pub h: usize,
+    cells: Vec<TileBits>,
+    // cells that neither shrink nor hold their neighbours back, for ones the solver gave up on
+    pinned: Vec<bool>,
+    // scratch for propagate, so it doesn't allocate a whole map's worth every placement
+    queued: Vec<bool>,
+}
+
+impl Domains {
+    pub fn new(tileset: &TileSet, w: usize, h: usize) -> Domains {
+        Domains {
+            w,
+            h,
+            cells: vec![TileBits::full(tileset.vec.len()); w*h],
+            pinned: vec![false; w*h],
+            queued: vec![false; w*h],
+        }
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> &TileBits {
+        &self.cells[j*self.w + i]
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, domain: TileBits) {
+        self.cells[j*self.w + i] = domain;
+    }
+
+    pub fn pin(&mut self, i: usize, j: usize, pinned: bool) {
+        self.pinned[j*self.w + i] = pinned;
+    }
+
+    pub fn is_pinned(&self, i: usize, j: usize) -> bool {
+        self.pinned[j*self.w + i]
+    }
+
+    // AC-3 starting from the cells in queue, which have just changed. shrunk gets told about every
+    // cell that loses something, and which of its sides it lost it through. Stops at the first cell
+    // left with nothing and gives it back
+    pub fn propagate(&mut self, tileset: &TileSet, queue: &mut VecDeque<(usize, usize)>,
+            mut shrunk: impl FnMut(usize, usize, Dir, &TileBits)) -> Result<(), (usize, usize)> {
+        for &(i, j) in queue.iter() {
+            self.queued[j*self.w + i] = true;
+        }
+
+        while let Some((i, j)) = queue.pop_front() {
+            self.queued[j*self.w + i] = false;
+            if self.is_pinned(i, j) {
+                continue;
+            }
+            for (dir, neighbour) in grid_neighbours(self.w, self.h, j*self.w + i) {
+                let (ni, nj) = (neighbour % self.w, neighbour / self.w);
+                if self.is_pinned(ni, nj) {
+                    continue;
+                }
+                let support = tileset.index.support(self.get(i, j), dir);
+                let before = self.get(ni, nj).count();
+                let neighbour = &mut self.cells[nj*self.w + ni];
+                neighbour.intersect(&support);
+                let after = neighbour.count();
+                if after == before {
+                    continue;
+                }
+                shrunk(ni, nj, dir.opposite(), neighbour);
+                if after == 0 {
+                    for (qi, qj) in queue.drain(..) {
+                        self.queued[qj*self.w + qi] = false;
+                    }
+                    return Err((ni, nj));
+                }
+                if !self.queued[nj*self.w + ni] {
+                    self.queued[nj*self.w + ni] = true;
+                    queue.push_back((ni, nj));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_propagate() {
+    use crate::image_output::*;
+
+    // vertical stripes, period 4: black, black, white, white. Each window is the only one with
+    // its rows, so one cell decides its whole column. Next door only has to share the border column
+    let mut image = ImageBuffer::new(8, 8);
+    for y in 0..8 {
+        for x in 0..8 {
+            image.set_px(x, y, if x % 4 < 2 { (0, 0, 0) } else { (255, 255, 255) });
+        }
+    }
+    let tileset = crate::overlap::tileset_from_example(&image, 3, crate::symmetry::Symmetry::None, true).unwrap();
+    assert_eq!(tileset.vec.len(), 4);
+
+    let (w, h) = (6, 4);
+    let mut domains = Domains::new(&tileset, w, h);
+    let mut single = TileBits::empty(4);
+    single.insert(0);
+    domains.set(2, 1, single.clone());
+    let mut shrunk = Vec::new();
+    let mut queue = VecDeque::from([(2, 1)]);
+    assert!(domains.propagate(&tileset, &mut queue, |i, j, _, _| shrunk.push((i, j))).is_ok());
+    for j in 0..h {
+        assert_eq!(domains.get(2, j), &single);
+        assert_eq!(domains.get(1, j).count(), 2);
+        assert_eq!(domains.get(3, j).count(), 2);
+    }
+    assert!(shrunk.contains(&(2, 3)) && shrunk.contains(&(1, 0)));
+
+    // anything else further down the column can't work, found without placing anything in between
+    let mut other = TileBits::empty(4);
+    other.insert(1);
+    let mut domains = Domains::new(&tileset, w, h);
+    domains.set(2, 0, single);
+    domains.set(2, 3, other);
+    let mut queue = VecDeque::from([(2, 0), (2, 3)]);
+    assert!(domains.propagate(&tileset, &mut queue, |_, _, _, _| {}).is_err());
+}
@@ -0,0 +1,288 @@
+use crate::colour::*;
+use crate::compat::*;
+use crate::greedy::*;
+use crate::grid::*;
+use crate::krand::*;
+use crate::local::*;
+use crate::tileset::*;
+
+// the mix the map should come out with, as fractions of the whole map
+#[derive(Clone, Debug)]
+pub enum Histogram {
+    // groups of tiles by name, a trailing * matches the start of a name (see tiles_matching).
+    // A tile in more than one group counts for the first. Tiles not in any group are left alone,
+    // so "30% forest 50% grass" leaves the other 20% to whatever fits
+    Tiles(Vec<(String, f32)>),
+    // pixels, each one counted for whichever of the colours it's closest to. Every pixel counts
+    // for something so list all the colours that matter, with 0.0 for ones to keep out
+    Colours(Vec<((u8, u8, u8), f32)>),
+}
+
+#[derive(Clone, Debug)]
+pub struct GeneticParams {
+    pub population: usize,
+    pub generations: usize,
+    // best few carried over untouched each generation
+    pub elite: usize,
+    // parents are the best of this many picked at random
+    pub tournament: usize,
+    pub crossover_rate: f32,
+    // biggest rectangle crossover swaps in, as a fraction of the map's width and height
+    pub max_patch: f32,
+    // fraction of cells resampled in each child, by weight from the tiles that fit their neighbours
+    pub mutation_rate: f32,
+    // fraction of cells in each child given whichever tile fits its neighbours best, half of them
+    // where the first parent had bad borders. Greedy rather than random, 0 leaves it out
+    pub repair_rate: f32,
+    pub target: Option<Histogram>,
+    // cost of being off target. The distance is summed over the histogram's entries and scaled by
+    // the number of cells, so at 0.5 a cell in the wrong group costs about the same as one bad border
+    pub histogram_weight: f32,
+}
+
+impl Default for GeneticParams {
+    fn default() -> GeneticParams {
+        GeneticParams {
+            population: 40,
+            generations: 2000,
+            elite: 2,
+            tournament: 3,
+            crossover_rate: 0.9,
+            max_patch: 0.5,
+            mutation_rate: 0.01,
+            repair_rate: 0.01,
+            target: None,
+            histogram_weight: 0.5,
+        }
+    }
+}
+
+// how much each tile puts into each of the histogram's entries
+fn tile_bins(tileset: &TileSet, target: &Histogram) -> Vec<Vec<f32>> {
+    let n = tileset.tile_size;
+    match target {
+        Histogram::Tiles(groups) => {
+            let mut bins = vec![vec![0.0; groups.len()]; tileset.vec.len()];
+            for (k, (pattern, _)) in groups.iter().enumerate().rev() {
+                for handle in tileset.tiles_matching(pattern) {
+                    bins[handle as usize].fill(0.0);
+                    bins[handle as usize][k] = 1.0;
+                }
+            }
+            bins
+        },
+        Histogram::Colours(colours) => tileset.vec.iter().map(|tile| {
+            let mut bins = vec![0.0; colours.len()];
+            for &px in &tile.px_colour[..n*n] {
+                let nearest = (0..colours.len())
+                    .min_by(|&a, &b| delta_e(px, colours[a].0).total_cmp(&delta_e(px, colours[b].0)));
+                if let Some(k) = nearest {
+                    bins[k] += 1.0 / (n*n) as f32;
+                }
+            }
+            bins
+        }).collect(),
+    }
+}
+
+fn target_fractions(target: &Histogram) -> Vec<f32> {
+    match target {
+        Histogram::Tiles(groups) => groups.iter().map(|g| g.1).collect(),
+        Histogram::Colours(colours) => colours.iter().map(|c| c.1).collect(),
+    }
+}
+
+fn histogram_with(bins: &[Vec<f32>], n_bins: usize, tiling: &[TileHandle]) -> Vec<f32> {
+    let mut hist = vec![0.0; n_bins];
+    for &handle in tiling {
+        for (h, b) in hist.iter_mut().zip(&bins[handle as usize]) {
+            *h += b;
+        }
+    }
+    hist.iter().map(|h| h / tiling.len() as f32).collect()
+}
+
+// what fraction of the map each of target's entries actually came out as
+pub fn histogram(tileset: &TileSet, tiling: &[TileHandle], target: &Histogram) -> Vec<f32> {
+    let fractions = target_fractions(target);
+    histogram_with(&tile_bins(tileset, target), fractions.len(), tiling)
+}
+
+struct Individual {
+    tiling: Vec<TileHandle>,
+    violations: usize,
+    // cells with a bad border, half the mutations go to these
+    conflicted: Vec<usize>,
+    // histogram entries summed over the cells, not divided through yet
+    counts: Vec<f32>,
+    // summed absolute difference from the target
+    distance: f32,
+    cost: f32,
+}
+
+fn next_rand(state: &mut u32) -> u32 {
+    *state = khash(state.wrapping_add(0x9E3779B9));
+    *state
+}
+
+// genetic algorithm over whole maps. Cost is the number of bad borders plus how far the mix of
+// tiles or colours is from the target, which is something the other solvers can't aim for: left to
+// the weights testbeachgrassforest comes out all forest or all beach. Children are a parent with a
+// rectangle copied over from the other parent, then a few cells resampled by weight from the tiles
+// their neighbours allow (any tile if none do). Then the repair step, if it's on, gives a few more
+// the tiles that fit their neighbours best, half of them where the first parent had bad borders,
+// and of those whichever bring the mix closest to the target. Gives back the cheapest map it saw
+pub fn genetic_tiling_with(tileset: &TileSet, w: usize, h: usize, seed: u32, params: &GeneticParams, budget: &Budget) -> Vec<TileHandle> {
+    let fractions = params.target.as_ref().map(target_fractions).unwrap_or_default();
+    let bins = params.target.as_ref().map(|t| tile_bins(tileset, t)).unwrap_or_default();
+
+    let distance = |counts: &[f32]| -> f32 {
+        counts.iter().zip(&fractions).fold(0.0, |acc, (c, f)| acc + (c / (w*h) as f32 - f).abs())
+    };
+    // moves counts from taking old out and putting new in
+    let swap = |counts: &mut [f32], old: TileHandle, new: TileHandle| {
+        if !counts.is_empty() {
+            for ((c, o), n) in counts.iter_mut().zip(&bins[old as usize]).zip(&bins[new as usize]) {
+                *c += n - o;
+            }
+        }
+    };
+
+    let evaluate = |tiling: Vec<TileHandle>| {
+        // every bad border gets seen from both sides
+        let per_cell: Vec<usize> = (0..w*h).map(|cell| cell_violations(tileset, &tiling, w, h, cell, tiling[cell])).collect();
+        let violations = per_cell.iter().sum::<usize>() / 2;
+        let conflicted = (0..w*h).filter(|&cell| per_cell[cell] > 0).collect();
+        let counts: Vec<f32> = if fractions.is_empty() {
+            Vec::new()
+        } else {
+            histogram_with(&bins, fractions.len(), &tiling).iter().map(|f| f * (w*h) as f32).collect()
+        };
+        let distance = distance(&counts);
+        let cost = violations as f32 + params.histogram_weight * distance * (w*h) as f32;
+        Individual { tiling, violations, conflicted, counts, distance, cost }
+    };
+    let by_cost = |a: &Individual, b: &Individual| a.cost.total_cmp(&b.cost);
+
+    let mut state = seed;
+    let mut population: Vec<Individual> = (0..params.population.max(2))
+        .map(|k| evaluate(random_fill(tileset, w, h, khash(seed ^ khash(k as u32)))))
+        .collect();
+    population.sort_by(by_cost);
+
+    let mut generation = 0;
+    let deadline = budget.deadline();
+    while generation < params.generations && !(population[0].violations == 0 && fractions.is_empty()) && budget.stop(generation, deadline).is_none() {
+        let tournament = |state: &mut u32| {
+            (0..params.tournament.max(1))
+                .map(|_| next_rand(state) as usize % population.len())
+                .min()
+                .unwrap()
+        };
+        let mut next: Vec<Individual> = Vec::with_capacity(population.len());
+        let mut children = Vec::new();
+        while children.len() + params.elite.min(population.len()) < population.len() {
+            // sorted, so the lowest index is the fittest
+            let (a, b) = (tournament(&mut state), tournament(&mut state));
+            let mut child = population[a].tiling.clone();
+            let mut counts = population[a].counts.clone();
+
+            if uniform_f32(next_rand(&mut state)) < params.crossover_rate {
+                let pw = 1 + next_rand(&mut state) as usize % ((w as f32 * params.max_patch) as usize).max(1);
+                let ph = 1 + next_rand(&mut state) as usize % ((h as f32 * params.max_patch) as usize).max(1);
+                let x0 = next_rand(&mut state) as usize % (w - pw.min(w) + 1);
+                let y0 = next_rand(&mut state) as usize % (h - ph.min(h) + 1);
+                for j in y0..(y0 + ph).min(h) {
+                    for i in x0..(x0 + pw).min(w) {
+                        let new = population[b].tiling[j*w + i];
+                        swap(&mut counts, child[j*w + i], new);
+                        child[j*w + i] = new;
+                    }
+                }
+            }
+
+            let n_mutations = ((w*h) as f32 * params.mutation_rate).ceil() as usize;
+            for _ in 0..n_mutations {
+                let cell = next_rand(&mut state) as usize % (w*h);
+                let mut neighbours = [HANDLE_UNSET; 4];
+                for (dir, neighbour) in grid_neighbours(w, h, cell) {
+                    neighbours[dir.idx()] = child[neighbour];
+                }
+                let mut choices = tileset.candidates(&neighbours);
+                if choices.count() == 0 {
+                    choices = TileBits::full(tileset.vec.len());
+                }
+                let new = tileset.pick_weighted(&choices, next_rand(&mut state)).unwrap();
+                swap(&mut counts, child[cell], new);
+                child[cell] = new;
+            }
+
+            let n_repairs = ((w*h) as f32 * params.repair_rate).ceil() as usize;
+            for _ in 0..n_repairs {
+                let r = next_rand(&mut state);
+                let conflicted = &population[a].conflicted;
+                let cell = if r & 1 == 0 && !conflicted.is_empty() {
+                    conflicted[khash(r) as usize % conflicted.len()]
+                } else {
+                    khash(r) as usize % (w*h)
+                };
+                let mut choices = least_conflicted(tileset, &child, w, h, cell);
+                if !counts.is_empty() {
+                    // of those, the ones that bring the mix closest to the target. That's what
+                    // moves the edges of regions about, a tile inside one only fits itself
+                    let after: Vec<(TileHandle, f32)> = choices.iter().map(|t| {
+                        let mut c = counts.clone();
+                        swap(&mut c, child[cell], t);
+                        (t, distance(&c))
+                    }).collect();
+                    let closest = after.iter().fold(f32::MAX, |acc, &(_, d)| acc.min(d));
+                    for (t, d) in after {
+                        if d > closest {
+                            choices.remove(t);
+                        }
+                    }
+                }
+                let new = tileset.pick_weighted(&choices, next_rand(&mut state)).unwrap();
+                swap(&mut counts, child[cell], new);
+                child[cell] = new;
+            }
+            children.push(child);
+        }
+
+        next.extend(population.drain(..params.elite.min(population.len())));
+        next.extend(children.into_iter().map(evaluate));
+        next.sort_by(by_cost);
+        population = next;
+        generation += 1;
+    }
+
+    let best = population.swap_remove(0);
+    println!("genetic -- generations: {} violations: {} histogram distance: {:.3}", generation, best.violations, best.distance);
+    best.tiling
+}
+
+#[test]
+fn test_genetic() {
+    let tileset = load_tileset("test_tilesets/roads.png", 3).unwrap();
+    let (w, h) = (12, 10);
+    let tiling = genetic_tiling_with(&tileset, w, h, 69, &GeneticParams::default(), &Budget::default());
+    assert_eq!(count_violations(&tileset, &tiling, w, h), 0);
+    // without the repair step it's down to crossover and resampling, slower but it still gets better
+    let unrepaired = genetic_tiling_with(&tileset, w, h, 69, &GeneticParams { repair_rate: 0.0, generations: 300, ..GeneticParams::default() }, &Budget::default());
+    assert!(count_violations(&tileset, &unrepaired, w, h) < count_violations(&tileset, &random_fill(&tileset, w, h, 69), w, h));
+
+    // aiming for a mix pulls the map towards it, compared to no target at all
+    let tileset = load_tileset("test_tilesets/testbeachgrassforest.png", 3).unwrap();
+    let (w, h) = (16, 16);
+    let forest = Histogram::Colours(vec![((12, 130, 0), 0.5), ((55, 224, 39), 0.5), ((224, 209, 39), 0.0), ((0, 0, 255), 0.0)]);
+    let params = GeneticParams { generations: 300, ..GeneticParams::default() };
+    let free = genetic_tiling_with(&tileset, w, h, 69, &params, &Budget::default());
+    let aimed = genetic_tiling_with(&tileset, w, h, 69, &GeneticParams { target: Some(forest.clone()), ..params }, &Budget::default());
+    let off = |tiling: &[TileHandle]| (histogram(&tileset, tiling, &forest)[0] - 0.5).abs();
+    assert!(off(&aimed) < off(&free));
+    assert!(off(&aimed) < 0.1);
+
+    let tiles = Histogram::Tiles(vec![("tile_0_1*".to_string(), 1.0), ("tile_0_*".to_string(), 0.0)]);
+    let all_01: Vec<TileHandle> = vec![tileset.tiles_matching("tile_0_1")[0]; 4];
+    assert_eq!(histogram(&tileset, &all_01, &tiles), vec![1.0, 0.0]);
+}
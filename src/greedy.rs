@@ -0,0 +1,391 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::grid::*;
+use crate::krand::*;
+use crate::compat::*;
+use crate::domain::*;
+use crate::priority_queue::*;
+use crate::rollback::*;
+use crate::tileset::*;
+
+// which undecided cell gets filled next
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CellHeuristic {
+    // fewest tiles left. Ties go in whatever order the heap has them, which shows up as scanlines
+    #[default]
+    Count,
+    // lowest weighted Shannon entropy of what's left, so a cell down to one likely tile and a few
+    // rare ones goes before one with an even spread. Plus a little noise seeded per cell so ties
+    // come out scattered over the map
+    Entropy,
+}
+
+// entropy ties are broken by at most this much
+const ENTROPY_NOISE: f32 = 1e-4;
+
+// shared between a run and whoever might want to stop it, from any thread. Clones are the same token
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+    // cancelling this cancels us too
+    parent: Option<Box<CancelToken>>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed) || self.parent.as_ref().is_some_and(|parent| parent.is_cancelled())
+    }
+
+    // a token that's cancelled along with this one, but can also be cancelled on its own
+    pub fn child(&self) -> CancelToken {
+        CancelToken { flag: Arc::default(), parent: Some(Box::new(self.clone())) }
+    }
+
+    // cancelled when either this or other is. Cancelling it cancels this one
+    pub fn joined(&self, other: &CancelToken) -> CancelToken {
+        let parent = match &self.parent {
+            Some(parent) => parent.joined(other),
+            None => other.clone(),
+        };
+        CancelToken { flag: self.flag.clone(), parent: Some(Box::new(parent)) }
+    }
+}
+
+// why a run gave up before the queue emptied
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Steps,
+    Time,
+    Cancelled,
+}
+
+// limits on a whole solve, SolverConfig has one for whichever solver it goes to. What counts as a
+// step is up to the solver: a placement or rollback, a move, an iteration, a generation
+#[derive(Clone, Debug, Default)]
+pub struct Budget {
+    pub max_steps: Option<usize>,
+    pub time_limit: Option<Duration>,
+    pub cancel: Option<CancelToken>,
+}
+
+impl Budget {
+    // the same but with no more than max_steps
+    pub fn with_steps(&self, max_steps: usize) -> Budget {
+        Budget { max_steps: Some(self.max_steps.map_or(max_steps, |m| m.min(max_steps))), ..self.clone() }
+    }
+
+    // taken when the solve starts, for stop
+    pub fn deadline(&self) -> Option<Instant> {
+        self.time_limit.map(|limit| Instant::now() + limit)
+    }
+
+    // why to give up after steps steps, None to keep going. Checked before every step
+    pub fn stop(&self, steps: usize, deadline: Option<Instant>) -> Option<StopReason> {
+        if self.max_steps.is_some_and(|max_steps| steps >= max_steps) {
+            Some(StopReason::Steps)
+        } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            Some(StopReason::Time)
+        } else if self.cancel.as_ref().is_some_and(|cancel| cancel.is_cancelled()) {
+            Some(StopReason::Cancelled)
+        } else {
+            None
+        }
+    }
+
+    // params with their limits cut down to these
+    pub fn limit(&self, params: &GreedyParams) -> GreedyParams {
+        fn min<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+        let cancel = match (&params.cancel, &self.cancel) {
+            (Some(a), Some(b)) => Some(a.joined(b)),
+            (a, b) => a.clone().or(b.clone()),
+        };
+        GreedyParams {
+            max_steps: min(params.max_steps, self.max_steps),
+            time_limit: min(params.time_limit, self.time_limit),
+            cancel,
+            ..params.clone()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GreedyParams {
+    // None goes with whatever the tileset says
+    pub rollback: Option<RollbackPolicy>,
+    pub heuristic: CellHeuristic,
+    // placements and rollbacks before the run is abandoned, None for no limit
+    pub max_steps: Option<usize>,
+    // wall clock for the whole run, None for no limit
+    pub time_limit: Option<Duration>,
+    // checked once a step, cancelling it abandons the run like running out of steps
+    pub cancel: Option<CancelToken>,
+}
+
+pub struct GreedyRun {
+    // anything not placed when the run was abandoned is HANDLE_UNSET, which renders magenta
+    pub tiles: Vec<TileHandle>,
+    pub steps: usize,
+    // None if it got to the end of the queue
+    pub stopped: Option<StopReason>,
+}
+
+impl GreedyRun {
+    // failed and never placed cells
+    pub fn bad_cells(&self) -> usize {
+        self.tiles.iter().filter(|&&t| t == HANDLE_FAILED_PLACEMENT || t == HANDLE_UNSET).count()
+    }
+
+    // filled the whole map without being stopped, what restarts and portfolios are after
+    pub fn succeeded(&self) -> bool {
+        self.stopped.is_none() && self.bad_cells() == 0
+    }
+}
+
+// the original solver. Fills the most constrained cell next, picking from what's left of its domain
+// by weight, and when a cell runs out of options clears the area around it and tries again, how
+// much depending on the rollback policy and how many times that cell has been cleared already
+pub fn greedy_run(tileset: &TileSet, w: usize, h: usize, seed: u32, params: &GreedyParams) -> GreedyRun {
+    let rollback = params.rollback.as_ref().unwrap_or(&tileset.rollback);
+    let heuristic = params.heuristic;
+    let budget = Budget { max_steps: params.max_steps, time_limit: params.time_limit, cancel: params.cancel.clone() };
+    let deadline = budget.deadline();
+    // pq key for a cell with domain d, smallest goes first
+    let w_log_w: Vec<f32> = tileset.vec.iter().map(|t| if t.weight > 0.0 { t.weight * t.weight.ln() } else { 0.0 }).collect();
+    let priority = |i: usize, j: usize, d: &TileBits| -> u32 {
+        match heuristic {
+            CellHeuristic::Count => d.count() as u32,
+            CellHeuristic::Entropy => {
+                // H = ln(sum w) - sum(w ln w) / sum w
+                let (sum, sum_log) = d.iter().fold((0.0f32, 0.0f32), |(s, l), t| (s + tileset.vec[t as usize].weight, l + w_log_w[t as usize]));
+                let entropy = if sum > 0.0 { sum.ln() - sum_log / sum } else { 0.0 };
+                let noise = ENTROPY_NOISE * uniform_f32(seed ^ khash((j*w + i) as u32));
+                // non negative floats sort the same as their bits
+                (entropy.max(0.0) + noise).to_bits()
+            },
+        }
+    };
+
+    // tracks rerolls
+    let mut generation_grid = Grid::new(w, h, 0u32);
+
+    // initalize the output grid
+    let mut output_grid = Grid::new(w, h, HANDLE_UNSET);
+
+    // what could still go in each cell
+    let mut domains = Domains::new(tileset, w, h);
+
+    // per side of each cell, the placement that last took something out of its domain through that
+    // side, for RollbackShape::Provenance. Kept after the cell gets placed so it can go more than
+    // one deep
+    let mut provenance = Grid::new(w, h, [None::<(usize, usize)>; 4]);
+
+    // set up pq, keyed by how undecided each cell is
+    let mut pq = PriorityQueue::new();
+    let mut queue = VecDeque::new();
+    for tile_i in 0..w {
+        for tile_j in 0..h {
+            pq.set(priority(tile_i, tile_j, domains.get(tile_i, tile_j)), (tile_i, tile_j));
+            queue.push_back((tile_i, tile_j));
+        }
+    }
+
+    // how many rollbacks of each radius
+    let mut rollbacks: Vec<usize> = Vec::new();
+    let mut place_failures = 0;
+    let mut steps = 0;
+    let mut stopped;
+
+    // a cell that ran out of options, either when it came up or while propagating
+    let mut contradiction = domains.propagate(tileset, &mut queue, |i, j, _, d| pq.set(priority(i, j, d), (i, j))).err();
+
+    loop {
+        stopped = budget.stop(steps, deadline);
+        if stopped.is_some() {
+            break;
+        }
+
+        if let Some((i, j)) = contradiction.take() {
+            let placed = |ci: usize, cj: usize| {
+                let handle = output_grid.get(ci, cj);
+                handle != HANDLE_UNSET && handle != HANDLE_FAILED_PLACEMENT
+            };
+            let was_placed = placed(i, j);
+            let radius = rollback.radius(generation_grid.get(i, j));
+            let cleared = match radius {
+                Some(r) => {
+                    if rollbacks.len() <= r {
+                        rollbacks.resize(r + 1, 0);
+                    }
+                    rollbacks[r] += 1;
+                    steps += 1;
+                    let contributors = |ci: usize, cj: usize| provenance.get(ci, cj).iter().flatten().copied().collect();
+                    rollback.cells(w, h, (i, j), r, placed, contributors)
+                },
+                // out of rerolls. A placed tile that got squeezed out is taken off and the cell
+                // comes round again, it's only given up on once it's empty
+                None if was_placed => {
+                    steps += 1;
+                    vec![(i, j)]
+                },
+                None => Vec::new(),
+            };
+            for &(ci, cj) in cleared.iter() {
+                *generation_grid.get_mut(ci, cj) += 1;
+                output_grid.set(ci, cj, HANDLE_UNSET);
+                domains.pin(ci, cj, false);
+            }
+            let mut around_from = cleared;
+            if radius.is_none() && !was_placed {
+                place_failures += 1;
+                output_grid.set(i, j, HANDLE_FAILED_PLACEMENT);
+                domains.pin(i, j, true);
+                // its neighbours were narrowed against what it had left, which no longer counts
+                around_from.push((i, j));
+            }
+
+            // what was pruned in and around the cleared area might have been down to tiles that are
+            // gone now, so start those cells again from just the placed tiles next to them and
+            // let everything around pull them back in
+            let mut reset: Vec<(usize, usize)> = Vec::new();
+            let mut empty = None;
+            for &(ci, cj) in around_from.iter() {
+                let around = DIRS.iter().filter_map(|&dir| output_grid.neighbour(ci, cj, dir).map(|_| idx_in_dir(ci, cj, dir)));
+                for (ni, nj) in std::iter::once((ci, cj)).chain(around) {
+                    if output_grid.get(ni, nj) == HANDLE_UNSET && !reset.contains(&(ni, nj)) {
+                        let domain = tileset.candidates(&neighbour_handles(&output_grid, ni, nj));
+                        if domain.count() == 0 && empty.is_none() {
+                            empty = Some((ni, nj));
+                        }
+                        pq.set(priority(ni, nj, &domain), (ni, nj));
+                        domains.set(ni, nj, domain);
+                        reset.push((ni, nj));
+                        // the placed neighbours are all that's narrowing it now
+                        let mut sides = [None; 4];
+                        for dir in DIRS {
+                            if output_grid.neighbour(ni, nj, dir).is_some_and(|t| t != HANDLE_UNSET && t != HANDLE_FAILED_PLACEMENT) {
+                                sides[dir.idx()] = Some(idx_in_dir(ni, nj, dir));
+                            }
+                        }
+                        provenance.set(ni, nj, sides);
+                    }
+                }
+            }
+            // propagating from an empty cell would empty everything around it too
+            if empty.is_some() {
+                contradiction = empty;
+                continue;
+            }
+            let mut queue: VecDeque<(usize, usize)> = reset.iter().copied().collect();
+            for &(ri, rj) in reset.iter() {
+                for dir in DIRS {
+                    if output_grid.neighbour(ri, rj, dir).is_some() {
+                        queue.push_back(idx_in_dir(ri, rj, dir));
+                    }
+                }
+            }
+            contradiction = domains.propagate(tileset, &mut queue, |i, j, _, d| pq.set(priority(i, j, d), (i, j))).err();
+            continue;
+        }
+
+        let Some((i, j)) = pq.remove_min() else {
+            break;
+        };
+        if output_grid.get(i, j) != HANDLE_UNSET {
+            // given up on while it was still queued
+            continue;
+        }
+
+        let gen = generation_grid.get(i, j);
+        if let Some(handle) = tileset.pick_weighted(
+                domains.get(i, j),
+                seed.wrapping_add(i as u32)
+                    .wrapping_add(0xF686CB1A_u32.wrapping_mul(j as u32))
+                    .wrapping_add(gen.wrapping_mul(0xCB497A23))
+            ) {
+
+            output_grid.set(i, j, handle);
+            steps += 1;
+            let mut single = TileBits::empty(tileset.vec.len());
+            single.insert(handle);
+            domains.set(i, j, single);
+            let mut queue = VecDeque::from([(i, j)]);
+            // a placed cell isn't in the pq any more, don't put it back
+            contradiction = domains.propagate(tileset, &mut queue, |ni, nj, side, d| {
+                provenance.get_mut(ni, nj)[side.idx()] = Some((i, j));
+                if output_grid.get(ni, nj) == HANDLE_UNSET {
+                    pq.set(priority(ni, nj, d), (ni, nj));
+                }
+            }).err();
+        } else {
+            contradiction = Some((i, j));
+        }
+    }
+
+    match stopped {
+        Some(reason) => println!("difficulty -- rollbacks by radius: {:?} fail: {} stopped: {:?} after {} steps", rollbacks, place_failures, reason, steps),
+        None => println!("difficulty -- rollbacks by radius: {:?} fail: {}", rollbacks, place_failures),
+    }
+    GreedyRun { tiles: output_grid.elements, steps, stopped }
+}
+
+#[test]
+fn test_greedy_run() {
+    use crate::solver::*;
+
+    let tileset = load_tileset("test_tilesets/testvillage.png", 3).unwrap();
+    let config = SolverConfig { w: 30, h: 30, seed: 69, budget: Budget::default() };
+    for shape in [RollbackShape::Square, RollbackShape::Diamond, RollbackShape::Constraining, RollbackShape::Provenance] {
+        let rollback = RollbackPolicy { shape, ..RollbackPolicy::default() };
+        let params = GreedyParams { rollback: Some(rollback), ..GreedyParams::default() };
+        let result = GreedySolver { params }.solve(&tileset, &config);
+        assert!(result.success, "{:?}", shape);
+    }
+    let params = GreedyParams { heuristic: CellHeuristic::Entropy, ..GreedyParams::default() };
+    let result = GreedySolver { params }.solve(&tileset, &config);
+    assert!(result.success);
+
+    // giving up almost straight away leaves failed cells, but never a tile that doesn't fit
+    let roads = load_tileset("test_tilesets/roads.png", 3).unwrap();
+    for shape in [RollbackShape::Square, RollbackShape::Provenance] {
+        let rollback = RollbackPolicy { schedule: vec![(2, 1)], shape };
+        let params = GreedyParams { rollback: Some(rollback), ..GreedyParams::default() };
+        let result = GreedySolver { params }.solve(&roads, &config);
+        assert_eq!((result.stats.violations, result.stats.unset), (0, 0), "{:?}", shape);
+    }
+
+    // out of steps leaves the rest unset
+    let run = greedy_run(&tileset, 30, 30, 69, &GreedyParams { max_steps: Some(100), ..GreedyParams::default() });
+    assert_eq!(run.stopped, Some(StopReason::Steps));
+    assert_eq!(run.steps, 100);
+    let unset = run.tiles.iter().filter(|&&t| t == HANDLE_UNSET).count();
+    assert!(unset >= 30*30 - 100);
+
+    // same for the clock and being cancelled, which here happens before it starts
+    let run = greedy_run(&tileset, 30, 30, 69, &GreedyParams { time_limit: Some(Duration::ZERO), ..GreedyParams::default() });
+    assert_eq!((run.stopped, run.steps), (Some(StopReason::Time), 0));
+    let cancel = CancelToken::default();
+    cancel.clone().cancel();
+    let run = greedy_run(&tileset, 30, 30, 69, &GreedyParams { cancel: Some(cancel.clone()), ..GreedyParams::default() });
+    assert_eq!((run.stopped, run.steps), (Some(StopReason::Cancelled), 0));
+    assert!(run.tiles.iter().all(|&t| t == HANDLE_UNSET));
+
+    // a budget on top of the run's own limits, the smaller of each and either token
+    let own = CancelToken::default();
+    let params = GreedyParams { max_steps: Some(100), time_limit: Some(Duration::from_secs(1)), cancel: Some(own.clone()), ..GreedyParams::default() };
+    let budget = Budget { max_steps: Some(1000), time_limit: Some(Duration::ZERO), cancel: Some(cancel) };
+    let limited = budget.limit(&params);
+    assert_eq!((limited.max_steps, limited.time_limit), (Some(100), Some(Duration::ZERO)));
+    assert!(limited.cancel.unwrap().is_cancelled() && !own.is_cancelled());
+}
@@ -4,7 +4,7 @@ pub struct Grid<T> {
     pub elements: Vec<T>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Dir {
     North,
     East,
@@ -12,7 +12,19 @@ pub enum Dir {
     West,
 }
 
+pub const DIRS: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+
 impl Dir {
+    // for indexing per direction tables, same order as DIRS
+    pub fn idx(&self) -> usize {
+        match self {
+            Dir::North => 0,
+            Dir::East => 1,
+            Dir::South => 2,
+            Dir::West => 3,
+        }
+    }
+
     pub fn opposite(&self) -> Dir {
         match self {
             Dir::North => Dir::South,
@@ -23,6 +35,21 @@ impl Dir {
     }
 }
 
+// the neighbours of cell (row major index j*w + i) that are on the grid, with the direction they're in
+pub fn grid_neighbours(w: usize, h: usize, cell: usize) -> impl Iterator<Item = (Dir, usize)> {
+    let (i, j) = (cell % w, cell / w);
+    DIRS.into_iter().filter_map(move |dir| {
+        let off = i == 0 && dir == Dir::West || j == 0 && dir == Dir::North ||
+            i + 1 == w && dir == Dir::East || j + 1 == h && dir == Dir::South;
+        if off {
+            None
+        } else {
+            let (ni, nj) = idx_in_dir(i, j, dir);
+            Some((dir, nj*w + ni))
+        }
+    })
+}
+
 pub fn idx_in_dir(i: usize, j: usize, dir: Dir) -> (usize, usize) {
     match dir {
         Dir::North => (i, j-1),
@@ -43,60 +70,37 @@ impl<T: std::marker::Copy> Grid<T> {
     }
 
     pub fn get(&self, i: usize, j: usize) -> T {
-        self.elements[i*self.w + j]
+        self.elements[j*self.w + i]
     }
 
     pub fn set(&mut self, i: usize, j: usize, elem: T) {
-        self.elements[i*self.w + j] = elem;
+        self.elements[j*self.w + i] = elem;
     }
 
     pub fn get_mut(&mut self, i: usize, j: usize) -> &mut T {
-        &mut self.elements[i*self.w + j]
+        &mut self.elements[j*self.w + i]
     }
 
-    pub fn offset_mut(&mut self, i: usize, j: usize, oi: i32, oj: i32) -> Option<&mut T> {
-        let ti = i as i32 + oi;
-        let tj = j as i32 + oj;
-        if ti < 0 || ti >= self.w as i32 {
-            return None;
-        }
-        if tj < 0 || tj >= self.h as i32 {
-            return None;
-        } 
-
-        return Some(&mut self.elements[ti as usize*self.w + tj as usize])
-    }
-
-    pub fn neighbour_mut(&mut self, i: usize, j: usize, dir: Dir) -> Option<&mut T> {
+    pub fn neighbour(&self, i: usize, j: usize, dir: Dir) -> Option<T> {
         match dir {
-            Dir::North => {
-                if j > 0 {
-                    Some(&mut self.elements[i*self.w + (j-1)])
-                } else {
-                    None
-                }
-            },
-            Dir::South => {
-                if j < self.h - 1 {
-                    Some(&mut self.elements[i*self.w + (j+1)])
-                } else {
-                    None
-                }
-            },
-            Dir::West => {
-                if i > 0 {
-                    Some(&mut self.elements[(i-1)*self.w + j])
-                } else {
-                    None
-                }
-            },
-            Dir::East => {
-                if i < self.w - 1 {
-                    Some(&mut self.elements[(i+1)*self.w + j])
-                } else {
-                    None
-                }
-            },
+            Dir::North => if j > 0 { Some(self.get(i, j-1)) } else { None },
+            Dir::South => if j < self.h - 1 { Some(self.get(i, j+1)) } else { None },
+            Dir::West => if i > 0 { Some(self.get(i-1, j)) } else { None },
+            Dir::East => if i < self.w - 1 { Some(self.get(i+1, j)) } else { None },
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_grid_row_major() {
+    // i is the column and j the row, whatever the shape. This used to index i*w + j, which only
+    // works out on square grids
+    let mut grid = Grid::new(3, 2, 0);
+    grid.set(2, 1, 5);
+    *grid.get_mut(1, 0) += 7;
+    assert_eq!(grid.elements, vec![0, 7, 0, 0, 0, 5]);
+    assert_eq!(grid.get(2, 1), 5);
+    assert_eq!(grid.neighbour(2, 0, Dir::South), Some(5));
+    assert_eq!(grid.neighbour(0, 0, Dir::East), Some(7));
+    assert_eq!(grid.neighbour(2, 1, Dir::East), None);
+}
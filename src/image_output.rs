@@ -7,6 +7,38 @@ pub struct ImageBuffer {
     pub w: usize,
     pub h: usize,
     pub pixels: Vec<(u8,u8,u8)>,
+    pub alpha: Vec<u8>, // only filled in by loading, everything else is opaque
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(std::io::Error),
+    Decode(png::DecodingError),
+    Unsupported(png::ColorType, png::BitDepth),
+}
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "couldn't read image: {}", e),
+            ImageError::Decode(e) => write!(f, "couldn't decode png: {}", e),
+            ImageError::Unsupported(colour, depth) => write!(f, "unsupported png format {:?} {:?}", colour, depth),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<std::io::Error> for ImageError {
+    fn from(e: std::io::Error) -> ImageError {
+        ImageError::Io(e)
+    }
+}
+
+impl From<png::DecodingError> for ImageError {
+    fn from(e: png::DecodingError) -> ImageError {
+        ImageError::Decode(e)
+    }
 }
 
 // should actually just use a vec u8 internally so no need to convert back and forth
@@ -17,6 +49,7 @@ impl ImageBuffer {
             w,
             h,
             pixels: vec![(0,0,0); w*h],
+            alpha: vec![255; w*h],
         }
     }
     pub fn set_px(&mut self, x: usize, y: usize, val: (u8, u8, u8)) {
@@ -26,32 +59,49 @@ impl ImageBuffer {
         self.pixels[y*self.w + x]
     }
     
-    pub fn new_from_file(path_str: &str) -> ImageBuffer {
-        let decoder = png::Decoder::new(File::open(path_str).unwrap());
-        let mut reader = decoder.read_info().unwrap();
+    // any png colour type and bit depth, palettes get expanded, grey gets spread across rgb and 16 bit gets cut to 8
+    pub fn new_from_file(path_str: &str) -> Result<ImageBuffer, ImageError> {
+        let mut decoder = png::Decoder::new(File::open(path_str)?);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
         // Allocate the output buffer.
         let mut buf = vec![0; reader.output_buffer_size()];
         // Read the next frame. An APNG might contain multiple frames.
-        let info = reader.next_frame(&mut buf).unwrap();
-        // Grab the bytes of the image.
-        let bytes = &buf[..info.buffer_size()];
-        let mut bytes_idx = 0;
-        // extra copy whatever idgaf
+        let info = reader.next_frame(&mut buf)?;
+        let samples = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::Rgb => 3,
+            png::ColorType::Rgba => 4,
+            png::ColorType::Indexed => return Err(ImageError::Unsupported(info.color_type, info.bit_depth)),
+        };
+        if info.bit_depth != png::BitDepth::Eight {
+            return Err(ImageError::Unsupported(info.color_type, info.bit_depth));
+        }
+
         let mut image_buffer = ImageBuffer::new(info.width as usize, info.height as usize);
         for j in 0..image_buffer.h {
+            let row = &buf[j*info.line_size..];
             for i in 0..image_buffer.w {
-                image_buffer.set_px(i, j, (bytes[bytes_idx], bytes[bytes_idx + 1], bytes[bytes_idx + 2]));
-                bytes_idx += 3;
+                let s = &row[i*samples..(i+1)*samples];
+                let (px, a) = match samples {
+                    1 => ((s[0], s[0], s[0]), 255),
+                    2 => ((s[0], s[0], s[0]), s[1]),
+                    3 => ((s[0], s[1], s[2]), 255),
+                    _ => ((s[0], s[1], s[2]), s[3]),
+                };
+                image_buffer.set_px(i, j, px);
+                image_buffer.alpha[j*image_buffer.w + i] = a;
             }
         }
-        image_buffer
+        Ok(image_buffer)
     }
     pub fn dump_to_file(&self, path_str: &str) {
         let path = Path::new(path_str);
         let file = File::create(path).unwrap();
-        let ref mut buf_writer = BufWriter::new(file);
+        let buf_writer = &mut BufWriter::new(file);
     
-        let mut data = vec![0u8; (3*self.w*self.h)];
+        let mut data = vec![0u8; 3*self.w*self.h];
         let mut data_index = 0;
         for px in self.pixels.iter() {
             data[data_index] = px.0;
@@ -81,37 +131,33 @@ impl ImageBuffer {
     }
 }
 
-pub fn dump_image(path_str: &str, run_data: &[u8], w: usize, h: usize) {
-    let path = Path::new(path_str);
-    let file = File::create(path).unwrap();
-    let ref mut buf_writer = BufWriter::new(file);
-
-    //let mut data = vec![0u8; (4*w*h)];
-
-    let mut encoder = png::Encoder::new(buf_writer, w as u32, h as u32);
-    encoder.set_color(png::ColorType::Rgba);
-    encoder.set_depth(png::BitDepth::Eight);
-    encoder.set_trns(vec!(0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8));
-    encoder.set_source_gamma(png::ScaledFloat::from_scaled(45455)); // 1.0 / 2.2, scaled by 100000
-    encoder.set_source_gamma(png::ScaledFloat::new(1.0 / 2.2));     // 1.0 / 2.2, unscaled, but rounded
-    let source_chromaticities = png::SourceChromaticities::new(     // Using unscaled instantiation here
-        (0.31270, 0.32900),
-        (0.64000, 0.33000),
-        (0.30000, 0.60000),
-        (0.15000, 0.06000)
-    );
-    encoder.set_source_chromaticities(source_chromaticities);
-    let mut writer = encoder.write_header().unwrap();
-
-    /*
-    for i in 0..w*h {
-        let div_factor = std::u32::MAX / 256;
-        let intensity = (run_data[i as usize] / div_factor) as u8;
-        data[4*i as usize + 0] = run_data[];
-        data[4*i as usize + 1] = intensity;
-        data[4*i as usize + 2] = intensity;
-        data[4*i as usize + 3] = 255;
-    }
-    */
-    writer.write_image_data(&run_data).unwrap(); // Save
-}
\ No newline at end of file
+#[test]
+fn test_load_png_formats() {
+    let path = std::env::temp_dir().join(format!("wanggen_formats_{}.png", std::process::id()));
+    let write = |colour: png::ColorType, depth: png::BitDepth, palette: Option<Vec<u8>>, data: &[u8]| {
+        let file = File::create(&path).unwrap();
+        let mut encoder = png::Encoder::new(BufWriter::new(file), 2, 1);
+        encoder.set_color(colour);
+        encoder.set_depth(depth);
+        if let Some(palette) = palette {
+            encoder.set_palette(palette);
+        }
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        ImageBuffer::new_from_file(path.to_str().unwrap()).unwrap()
+    };
+
+    let grey = write(png::ColorType::Grayscale, png::BitDepth::Eight, None, &[10, 200]);
+    assert_eq!(grey.pixels, [(10, 10, 10), (200, 200, 200)]);
+
+    let grey16 = write(png::ColorType::Grayscale, png::BitDepth::Sixteen, None, &[0x12, 0x34, 0xFF, 0xFF]);
+    assert_eq!(grey16.pixels, [(0x12, 0x12, 0x12), (0xFF, 0xFF, 0xFF)]);
+
+    let rgba = write(png::ColorType::Rgba, png::BitDepth::Eight, None, &[1, 2, 3, 4, 5, 6, 7, 0]);
+    assert_eq!(rgba.pixels, [(1, 2, 3), (5, 6, 7)]);
+    assert_eq!(rgba.alpha, [4, 0]);
+
+    let indexed = write(png::ColorType::Indexed, png::BitDepth::Four, Some(vec![9, 8, 7, 1, 2, 3]), &[0x10]);
+    assert_eq!(indexed.pixels, [(1, 2, 3), (9, 8, 7)]);
+
+    std::fs::remove_file(&path).unwrap();
+}
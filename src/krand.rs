@@ -10,10 +10,10 @@ pub fn khash(seed: u32) -> u32 {
     mangled ^= mangled.rotate_left(7);
     mangled = mangled.wrapping_mul(n3);
     mangled ^= mangled.rotate_right(9);
-    return mangled;
+    mangled
 }
 
 // 0..1
 pub fn uniform_f32(seed: u32) -> f32 {
-    khash(seed) as f32 / std::u32::MAX as f32
+    khash(seed) as f32 / u32::MAX as f32
 }
\ No newline at end of file
@@ -0,0 +1,138 @@
+use crate::grid::*;
+use crate::tileset::*;
+
+// a side that nothing can sit against
+pub struct UnpartneredSide {
+    pub dir: Dir,
+    pub side: String,
+    pub tiles: Vec<TileHandle>,
+}
+
+pub struct LintReport {
+    // tiles that can't go anywhere away from the edge of the map. Found by repeatedly throwing out
+    // tiles missing a neighbour on some side, so a tile only backed up by dead tiles is dead too
+    pub dead: Vec<TileHandle>,
+    pub unpartnered: Vec<UnpartneredSide>,
+    // (a, b, dir): nothing fits between a and b when b is 2 cells in direction dir of a
+    pub missing_transitions: Vec<(TileHandle, TileHandle, Dir)>,
+}
+
+impl LintReport {
+    // the README's sense: there is always a tile to bridge any two live tiles, so the
+    // greedy solver never paints itself into a corner with just its direct neighbours
+    pub fn complete(&self) -> bool {
+        self.missing_transitions.is_empty()
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.dead.is_empty() && self.unpartnered.is_empty()
+    }
+
+    pub fn print(&self, tileset: &TileSet) {
+        let name = |h: TileHandle| tileset.meta[h as usize].name.as_str();
+        for &h in self.dead.iter() {
+            println!("dead tile: {}", name(h));
+        }
+        for u in self.unpartnered.iter() {
+            let tiles: Vec<&str> = u.tiles.iter().map(|&h| name(h)).collect();
+            println!("nothing goes {:?} of side {} on {}", u.dir, u.side, tiles.join(", "));
+        }
+        println!("{} dead, {} unpartnered sides, {} missing transitions, {}",
+            self.dead.len(), self.unpartnered.len(), self.missing_transitions.len(),
+            if self.complete() { "complete" } else { "incomplete" });
+    }
+}
+
+// goes by the tileset's index, so any .rules allow and deny lines count the same as they do for
+// the solvers
+pub fn lint_tileset(tileset: &TileSet) -> LintReport {
+    let n_tiles = tileset.vec.len();
+    let fits = |a: usize, dir: Dir| tileset.index.fits(a as TileHandle, dir);
+
+    let mut alive = vec![true; n_tiles];
+    loop {
+        let mut changed = false;
+        for a in 0..n_tiles {
+            if alive[a] && DIRS.iter().any(|&dir| !fits(a, dir).iter().any(|b| alive[b as usize])) {
+                alive[a] = false;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let dead = (0..n_tiles).filter(|&a| !alive[a]).map(|a| a as TileHandle).collect();
+
+    let mut unpartnered: Vec<UnpartneredSide> = Vec::new();
+    for dir in DIRS {
+        for a in 0..n_tiles {
+            if !fits(a, dir).is_empty() {
+                continue;
+            }
+            let side = tileset.side_label(a as TileHandle, dir);
+            if let Some(u) = unpartnered.iter_mut().find(|u| u.dir == dir && u.side == side) {
+                u.tiles.push(a as TileHandle);
+            } else {
+                unpartnered.push(UnpartneredSide { dir, side, tiles: vec![a as TileHandle] });
+            }
+        }
+    }
+
+    let mut missing_transitions = Vec::new();
+    for dir in [Dir::East, Dir::South] {
+        for a in (0..n_tiles).filter(|&a| alive[a]) {
+            for b in (0..n_tiles).filter(|&b| alive[b]) {
+                let mut between = fits(a, dir).clone();
+                between.intersect(fits(b, dir.opposite()));
+                if between.is_empty() {
+                    missing_transitions.push((a as TileHandle, b as TileHandle, dir));
+                }
+            }
+        }
+    }
+
+    LintReport {
+        dead,
+        unpartnered,
+        missing_transitions,
+    }
+}
+
+#[test]
+fn test_lint() {
+    use crate::image_output::*;
+
+    // grass, water, and a water tile with one grass corner that nothing can join up with
+    let n = 3;
+    let g = (0, 255, 0);
+    let w = (0, 0, 255);
+    let mut image = ImageBuffer::new(3*(n+1), n+1);
+    for y in 0..n {
+        for x in 0..n {
+            image.set_px(x, y, g);
+            image.set_px((n+1) + x, y, w);
+            image.set_px(2*(n+1) + x, y, if x == 0 && y == 0 { g } else { w });
+        }
+    }
+    let tileset = make_tileset(&image, n, None).unwrap();
+    let report = lint_tileset(&tileset);
+    // the corner has a mixed top and left side, nothing can go above or left of it
+    assert_eq!(report.dead, [2]);
+    assert!(report.unpartnered.iter().any(|u| u.dir == Dir::North && u.tiles == [2]));
+    // no way to get from grass to water
+    assert!(!report.complete());
+    assert!(report.missing_transitions.contains(&(0, 1, Dir::East)));
+
+    let vert = load_tileset("test_tilesets/vert.png", 3).unwrap();
+    assert!(lint_tileset(&vert).is_clean());
+
+    // rules count too: allowing grass next to water bridges the gap, denying the corner's only
+    // neighbours kills grass as well
+    let mut ruled = make_tileset(&image, n, None).unwrap();
+    ruled.set_rules(Some(crate::rules::AdjacencyRules::parse("allow tile_0_0 any tile_1_0", &ruled).unwrap()));
+    let report = lint_tileset(&ruled);
+    assert!(!report.missing_transitions.contains(&(0, 1, Dir::East)));
+    ruled.set_rules(Some(crate::rules::AdjacencyRules::parse("deny tile_0_0 any tile_0_0", &ruled).unwrap()));
+    assert!(lint_tileset(&ruled).dead.contains(&0));
+}
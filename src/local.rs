@@ -0,0 +1,141 @@
+use crate::grid::*;
+use crate::krand::*;
+use crate::compat::*;
+use crate::greedy::*;
+use crate::tileset::*;
+
+// local search: start from a complete but wrong map and keep changing single cells until the
+// borders all fit. The map is always full so there's always something to show, just with some
+// mismatched borders if it runs out of steps
+
+// each cell drawn from the tile weights on their own, ignoring the neighbours
+pub fn random_fill(tileset: &TileSet, w: usize, h: usize, seed: u32) -> Vec<TileHandle> {
+    let all = TileBits::full(tileset.vec.len());
+    (0..w*h).map(|cell| tileset.pick_weighted(&all, khash(seed ^ khash(cell as u32))).unwrap()).collect()
+}
+
+// how many of cell's borders would be wrong with handle in it
+pub fn cell_violations(tileset: &TileSet, tiling: &[TileHandle], w: usize, h: usize, cell: usize, handle: TileHandle) -> usize {
+    grid_neighbours(w, h, cell)
+        .filter(|&(dir, neighbour)| !tileset.index.fits(handle, dir).contains(tiling[neighbour]))
+        .count()
+}
+
+// mismatched borders over the whole map, each counted once
+pub fn count_violations(tileset: &TileSet, tiling: &[TileHandle], w: usize, h: usize) -> usize {
+    (0..w*h).map(|cell| grid_neighbours(w, h, cell)
+        .filter(|&(dir, neighbour)| (dir == Dir::East || dir == Dir::South) &&
+            !tileset.index.fits(tiling[cell], dir).contains(tiling[neighbour]))
+        .count()).sum()
+}
+
+// the tiles that would leave cell with the fewest bad borders, including whatever's there now
+pub fn least_conflicted(tileset: &TileSet, tiling: &[TileHandle], w: usize, h: usize, cell: usize) -> TileBits {
+    let n_tiles = tileset.vec.len();
+    let mut best = TileBits::empty(n_tiles);
+    let mut best_violations = usize::MAX;
+    for handle in 0..n_tiles as TileHandle {
+        let v = cell_violations(tileset, tiling, w, h, cell, handle);
+        if v < best_violations {
+            best = TileBits::empty(n_tiles);
+            best_violations = v;
+        }
+        if v == best_violations {
+            best.insert(handle);
+        }
+    }
+    best
+}
+
+// cells with at least one bad border, kept so any one of them can be picked at random and
+// taken out or put back without searching
+pub struct ConflictSet {
+    cells: Vec<usize>,
+    pos: Vec<Option<usize>>,
+}
+
+impl ConflictSet {
+    pub fn new(tileset: &TileSet, tiling: &[TileHandle], w: usize, h: usize) -> ConflictSet {
+        let mut set = ConflictSet { cells: Vec::new(), pos: vec![None; w*h] };
+        for cell in 0..w*h {
+            set.update(tileset, tiling, w, h, cell);
+        }
+        set
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn get(&self, k: usize) -> usize {
+        self.cells[k]
+    }
+
+    // recheck one cell after it or a neighbour changed
+    pub fn update(&mut self, tileset: &TileSet, tiling: &[TileHandle], w: usize, h: usize, cell: usize) {
+        let conflicted = cell_violations(tileset, tiling, w, h, cell, tiling[cell]) > 0;
+        match (conflicted, self.pos[cell]) {
+            (true, None) => {
+                self.pos[cell] = Some(self.cells.len());
+                self.cells.push(cell);
+            },
+            (false, Some(k)) => {
+                self.cells.swap_remove(k);
+                if k < self.cells.len() {
+                    self.pos[self.cells[k]] = Some(k);
+                }
+                self.pos[cell] = None;
+            },
+            _ => {},
+        }
+    }
+
+    // after tiling[cell] changed
+    pub fn update_around(&mut self, tileset: &TileSet, tiling: &[TileHandle], w: usize, h: usize, cell: usize) {
+        self.update(tileset, tiling, w, h, cell);
+        for (_, neighbour) in grid_neighbours(w, h, cell) {
+            self.update(tileset, tiling, w, h, neighbour);
+        }
+    }
+}
+
+pub const MIN_CONFLICTS_STEPS: usize = 1_000_000;
+
+// hill climbing: fill at random, then keep picking a cell with a bad border and giving it whichever
+// tile leaves it the fewest bad borders, weighted random between ties. Ties include the tile already
+// there, which lets it wander along plateaus instead of stopping dead
+pub fn min_conflicts_tiling_steps(tileset: &TileSet, w: usize, h: usize, seed: u32, budget: &Budget) -> Vec<TileHandle> {
+    let mut tiling = random_fill(tileset, w, h, seed);
+    let mut conflicts = ConflictSet::new(tileset, &tiling, w, h);
+
+    let mut steps = 0;
+    let deadline = budget.deadline();
+    while !conflicts.is_empty() && budget.stop(steps, deadline).is_none() {
+        let step_seed = khash(seed.wrapping_add(steps as u32));
+        let cell = conflicts.get(khash(step_seed) as usize % conflicts.len());
+
+        tiling[cell] = tileset.pick_weighted(&least_conflicted(tileset, &tiling, w, h, cell), step_seed).unwrap();
+        conflicts.update_around(tileset, &tiling, w, h, cell);
+        steps += 1;
+    }
+
+    println!("min conflicts -- steps: {} violations: {}", steps, count_violations(tileset, &tiling, w, h));
+    tiling
+}
+
+#[test]
+fn test_min_conflicts() {
+    let tileset = load_tileset("test_tilesets/roads.png", 3).unwrap();
+    let (w, h) = (16, 12);
+    let start = random_fill(&tileset, w, h, 69);
+    assert!(count_violations(&tileset, &start, w, h) > 0);
+
+    let tiling = min_conflicts_tiling_steps(&tileset, w, h, 69, &Budget::default().with_steps(200000));
+    assert_eq!(tiling.len(), w*h);
+    assert_eq!(count_violations(&tileset, &tiling, w, h), 0);
+    assert!(ConflictSet::new(&tileset, &tiling, w, h).is_empty());
+}
@@ -0,0 +1,174 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::colour::*;
+use crate::rollback::*;
+use crate::symmetry::*;
+
+// optional sidecar next to a tileset png, same name with .toml on the end instead:
+//
+// tile_size = 3
+// backend = "pixels"   # or "sockets", then every tile needs its sockets listed
+// asymmetric = ["road"]  # sockets that pair with their mirror image, road with road'
+// colour_classes = [["#37e027", "#38e027"]]  # border colours that count as the same
+// colour_tolerance = 2.3  # and so does anything closer than this in Lab (delta E)
+//
+// [rollback]         # how the greedy solver backs out of dead ends, see RollbackPolicy
+// schedule = [[100, 1], [1000, 2]]
+// shape = "square"
+//
+// [[tile]]
+// x = 2              # which cell in the sheet, counted in tiles not pixels
+// y = 0
+// name = "hut"
+// weight = 0.25
+// symmetry = "rotate"  # none, rotate, flip_lr, flip_ud, flip_both, all
+// tags = ["building"]
+// enabled = true
+// sockets = ["grass", "road", "grass", "road'"]  # north, east, south, west, read clockwise
+//
+// anything set here wins over the control pixels
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub tile_size: Option<usize>,
+    pub backend: Option<Backend>,
+    #[serde(default)]
+    pub asymmetric: Vec<String>,
+    #[serde(default)]
+    pub colour_classes: Vec<Vec<HexColour>>,
+    pub colour_tolerance: Option<f32>,
+    pub rollback: Option<RollbackPolicy>,
+    #[serde(default, rename = "tile")]
+    pub tiles: Vec<TileEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TileEntry {
+    pub x: usize,
+    pub y: usize,
+    pub name: Option<String>,
+    pub weight: Option<f32>,
+    pub symmetry: Option<SymmetryName>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub enabled: Option<bool>,
+    pub sockets: Option<[String; 4]>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    Pixels,
+    Sockets,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SymmetryName {
+    None,
+    Rotate,
+    FlipLr,
+    FlipUd,
+    FlipBoth,
+    All,
+}
+
+impl SymmetryName {
+    pub fn symmetry(self) -> Symmetry {
+        match self {
+            SymmetryName::None => Symmetry::None,
+            SymmetryName::Rotate => Symmetry::Rotate,
+            SymmetryName::FlipLr => Symmetry::FlipLR,
+            SymmetryName::FlipUd => Symmetry::FlipUD,
+            SymmetryName::FlipBoth => Symmetry::FlipBoth,
+            SymmetryName::All => Symmetry::All,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+impl std::fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ManifestError::Io(e) => write!(f, "couldn't read manifest: {}", e),
+            ManifestError::Parse(e) => write!(f, "bad manifest: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Manifest, ManifestError> {
+        toml::from_str(text).map_err(ManifestError::Parse)
+    }
+
+    // tileset.png -> tileset.toml, None if there isn't one
+    pub fn load_sidecar(png_path: &str) -> Result<Option<Manifest>, ManifestError> {
+        let path = Path::new(png_path).with_extension("toml");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(&path).map_err(ManifestError::Io)?;
+        Manifest::parse(&text).map(Some)
+    }
+
+    pub fn asymmetric_socket(&self, base: &str) -> bool {
+        self.asymmetric.iter().any(|name| name == base)
+    }
+
+    pub fn entry(&self, x: usize, y: usize) -> Option<&TileEntry> {
+        self.tiles.iter().rev().find(|e| e.x == x && e.y == y)
+    }
+
+    // entries pointing outside the sheet, or shadowed by a later entry for the same cell
+    pub fn unmatched_entries(&self, n_tiles_x: usize, n_tiles_y: usize) -> Vec<&TileEntry> {
+        self.tiles.iter().enumerate()
+            .filter(|(i, e)| {
+                e.x >= n_tiles_x || e.y >= n_tiles_y ||
+                self.tiles[i+1..].iter().any(|later| later.x == e.x && later.y == e.y)
+            })
+            .map(|(_, e)| e)
+            .collect()
+    }
+}
+
+#[test]
+fn test_manifest_parse() {
+    let m = Manifest::parse(r#"
+        tile_size = 5
+
+        [[tile]]
+        x = 1
+        y = 0
+        name = "hut"
+        weight = 0.25
+        symmetry = "flip_lr"
+        tags = ["building", "village"]
+
+        [[tile]]
+        x = 9
+        y = 9
+        enabled = false
+    "#).unwrap();
+    assert_eq!(m.tile_size, Some(5));
+    let hut = m.entry(1, 0).unwrap();
+    assert_eq!(hut.name.as_deref(), Some("hut"));
+    assert_eq!(hut.weight, Some(0.25));
+    assert_eq!(hut.symmetry.unwrap().symmetry(), Symmetry::FlipLR);
+    assert_eq!(hut.tags.len(), 2);
+    assert!(m.entry(0, 0).is_none());
+
+    let unmatched = m.unmatched_entries(4, 4);
+    assert_eq!(unmatched.len(), 1);
+    assert_eq!((unmatched[0].x, unmatched[0].y), (9, 9));
+
+    assert!(matches!(Manifest::parse("[[tile]]\nx = 0\ny = 0\nwieght = 2"), Err(ManifestError::Parse(_))));
+}
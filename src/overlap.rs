@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use crate::image_output::*;
+use crate::constraint::*;
+use crate::symmetry::*;
+use crate::tileset::*;
+
+// learn a tileset from an ordinary picture instead of a hand drawn sheet. Every n x n window of the
+// example becomes a tile, weighted by how often it shows up. Since neighbouring tiles share their
+// border row, windows n-1 apart in the example are exactly the ones that fit together, so the
+// output is built out of bits of the example that already sit next to each other somewhere.
+// wrap lets windows run off the right and bottom edges and come back around the other side
+pub fn tileset_from_example(image: &ImageBuffer, n: usize, symmetry: Symmetry, wrap: bool) -> Result<TileSet, TilesetError> {
+    if !(2..=MAX_TILE_SIZE).contains(&n) {
+        return Err(TilesetError::TileSize(n));
+    }
+
+    let (max_x, max_y) = if wrap {
+        (image.w, image.h)
+    } else {
+        ((image.w + 1).saturating_sub(n), (image.h + 1).saturating_sub(n))
+    };
+
+    // keep first seen order so the handles don't depend on hash order
+    let mut patterns: Vec<(TilePixels, f32)> = Vec::new();
+    let mut pattern_idx: HashMap<Vec<(u8, u8, u8)>, usize> = HashMap::new();
+
+    for y in 0..max_y {
+        for x in 0..max_x {
+            let mut px = [(0, 0, 0); MAX_TILE_PX];
+            for ty in 0..n {
+                for tx in 0..n {
+                    px[ty*n + tx] = image.get_px((x + tx) % image.w, (y + ty) % image.h);
+                }
+            }
+            for (_, _, variant) in symmetry_variants(n, px, symmetry) {
+                let key = variant[..n*n].to_vec();
+                if let Some(&idx) = pattern_idx.get(&key) {
+                    patterns[idx].1 += 1.0;
+                } else {
+                    pattern_idx.insert(key, patterns.len());
+                    patterns.push((variant, 1.0));
+                }
+            }
+        }
+    }
+
+    let mut t = TileSet::new(n);
+    for (i, (px, count)) in patterns.into_iter().enumerate() {
+        let constraint = TileConstraint::Pixels(constraint_from_px_colour(n, &px));
+        let mut drawn = px;
+        fill_wildcards(n, &mut drawn);
+        t.push(TileSpec {
+            px_colour: drawn,
+            weight: count,
+            constraint,
+        }, TileMeta {
+            name: format!("pattern_{}", i),
+            tags: Vec::new(),
+        });
+    }
+    t.build_index();
+    Ok(t)
+}
+
+#[test]
+fn test_tileset_from_example() {
+    // vertical stripes, 2 wide, period 4
+    let mut image = ImageBuffer::new(8, 8);
+    for y in 0..8 {
+        for x in 0..8 {
+            image.set_px(x, y, if x % 4 < 2 { (255, 255, 255) } else { (0, 0, 0) });
+        }
+    }
+    let tileset = tileset_from_example(&image, 3, Symmetry::None, true).unwrap();
+    // one window per horizontal phase
+    assert_eq!(tileset.vec.len(), 4);
+    assert_eq!(tileset.vec.iter().map(|t| t.weight).sum::<f32>(), 64.0);
+
+    let clipped = tileset_from_example(&image, 3, Symmetry::None, false).unwrap();
+    assert_eq!(clipped.vec.iter().map(|t| t.weight).sum::<f32>(), 36.0);
+
+    let rotated = tileset_from_example(&image, 3, Symmetry::Rotate, true).unwrap();
+    assert_eq!(rotated.vec.len(), 8);
+
+    // whatever it makes should stay stripy
+    let tiling = crate::greedy_run(&tileset, 10, 10, 69, &crate::GreedyParams::default()).tiles;
+    assert!(tiling.iter().all(|&h| h != HANDLE_FAILED_PLACEMENT));
+    for j in 0..10 {
+        for i in 1..10 {
+            let a = tileset.get_tilespec(tiling[j*10 + i - 1]);
+            let b = tileset.get_tilespec(tiling[j*10 + i]);
+            assert!(tile_constraint_fits(a.constraint, b.constraint, crate::grid::Dir::East));
+        }
+    }
+}
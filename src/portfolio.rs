@@ -0,0 +1,163 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::greedy::*;
+use crate::restart::*;
+use crate::rollback::*;
+use crate::tileset::*;
+
+// one greedy run in the portfolio
+#[derive(Clone, Debug)]
+pub struct PortfolioEntry {
+    pub seed: u32,
+    pub params: GreedyParams,
+}
+
+#[derive(Clone, Debug)]
+pub struct PortfolioParams {
+    // in order of preference, when more than one succeeds the first of them wins
+    pub entries: Vec<PortfolioEntry>,
+    // 0 for one per core
+    pub threads: usize,
+    // wall clock for the whole portfolio, on top of any limits the entries have. Once it's out
+    // which entries got anywhere depends on how fast things ran
+    pub time_limit: Option<Duration>,
+    // print a line about the winner at the end
+    pub verbose: bool,
+}
+
+impl PortfolioParams {
+    // both heuristics with each rollback shape, and n seeds of each
+    pub fn mixed(seed: u32, n: usize) -> PortfolioParams {
+        let shapes = [RollbackShape::Square, RollbackShape::Diamond, RollbackShape::Constraining, RollbackShape::Provenance];
+        let mut entries = Vec::new();
+        for k in 0..n {
+            for heuristic in [CellHeuristic::Count, CellHeuristic::Entropy] {
+                for shape in shapes {
+                    let rollback = RollbackPolicy { shape, ..RollbackPolicy::default() };
+                    entries.push(PortfolioEntry {
+                        seed: restart_seed(seed, k),
+                        params: GreedyParams { rollback: Some(rollback), heuristic, ..GreedyParams::default() },
+                    });
+                }
+            }
+        }
+        PortfolioParams { entries, threads: 0, time_limit: None, verbose: false }
+    }
+}
+
+pub struct PortfolioResult {
+    pub tiles: Vec<TileHandle>,
+    // index into the entries of the run the tiles came from
+    pub winner: usize,
+    // failed and unset, 0 when the winner filled the map
+    pub bad_cells: usize,
+}
+
+// runs the entries across threads. The first entry to fill the map stops every entry after it, the
+// ones before it carry on since they'd win if they succeed too. That way which one wins depends
+// only on the entries and never on which thread got there first: it's the earliest successful
+// entry or, if none of them succeed, the one with the fewest bad cells
+pub fn portfolio_tiling(tileset: &TileSet, w: usize, h: usize, params: &PortfolioParams) -> PortfolioResult {
+    let n = params.entries.len();
+    let threads = match params.threads {
+        0 => thread::available_parallelism().map_or(1, |p| p.get()),
+        t => t,
+    }.min(n.max(1));
+    // an entry's own token still works, cancelling it stops that entry along with ours
+    let stops: Vec<CancelToken> = params.entries.iter()
+        .map(|entry| entry.params.cancel.as_ref().map_or_else(CancelToken::default, |cancel| cancel.child()))
+        .collect();
+    let next = AtomicUsize::new(0);
+    let deadline = params.time_limit.map(|limit| Instant::now() + limit);
+
+    // (entry, bad cells, tiles) for every entry that wasn't stopped
+    let mut runs: Vec<(usize, usize, Vec<TileHandle>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(|| {
+            let mut done = Vec::new();
+            loop {
+                // entries get handed out in order, so a stopped one never has to be waited on
+                let k = next.fetch_add(1, Ordering::Relaxed);
+                if k >= n {
+                    break;
+                }
+                if stops[k].is_cancelled() {
+                    continue;
+                }
+                let entry = &params.entries[k];
+                // whichever's sooner of the entry's own limit and what's left of ours
+                let left = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+                let time_limit = match (entry.params.time_limit, left) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+                let run = greedy_run(tileset, w, h, entry.seed, &GreedyParams { cancel: Some(stops[k].clone()), time_limit, ..entry.params.clone() });
+                if run.stopped == Some(StopReason::Cancelled) {
+                    // stopped, something earlier already succeeded or the caller gave up
+                    continue;
+                }
+                if run.succeeded() {
+                    for stop in &stops[k + 1..] {
+                        stop.cancel();
+                    }
+                }
+                done.push((k, run.bad_cells(), run.tiles));
+            }
+            done
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+
+    // an entry with its own step limit can come back unfinished too, it just counts as its bad cells.
+    // A time limit would work the same but then the result depends on how fast things went
+    runs.sort_by_key(|&(k, bad_cells, _)| (bad_cells, k));
+    let result = match runs.into_iter().next() {
+        Some((winner, bad_cells, tiles)) => PortfolioResult { tiles, winner, bad_cells },
+        None => PortfolioResult { tiles: vec![HANDLE_UNSET; w*h], winner: 0, bad_cells: w*h },
+    };
+    if params.verbose {
+        println!("portfolio -- entries: {} threads: {} winner: {} bad cells: {}", n, threads, result.winner, result.bad_cells);
+    }
+    result
+}
+
+#[test]
+fn test_portfolio_tiling() {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<TileSet>();
+
+    let tileset = load_tileset("test_tilesets/testvillage.png", 3).unwrap();
+    let (w, h) = (20, 20);
+    let params = PortfolioParams { threads: 4, ..PortfolioParams::mixed(69, 2) };
+    assert_eq!(params.entries.len(), 16);
+    let result = portfolio_tiling(&tileset, w, h, &params);
+    assert_eq!(result.bad_cells, 0);
+
+    // the same whatever the threads get up to
+    let again = portfolio_tiling(&tileset, w, h, &PortfolioParams { threads: 1, ..params.clone() });
+    assert_eq!((again.winner, &again.tiles), (result.winner, &result.tiles));
+    let first_success = params.entries.iter().position(|entry| greedy_run(&tileset, w, h, entry.seed, &entry.params).succeeded());
+    assert_eq!(first_success, Some(result.winner));
+
+    // nothing succeeds, the fewest bad cells wins
+    let mut short = params.clone();
+    for entry in short.entries.iter_mut() {
+        entry.params.max_steps = Some(50);
+    }
+    let result = portfolio_tiling(&tileset, w, h, &short);
+    assert!(result.bad_cells >= w*h - 50);
+
+    // the caller's token stops every entry, cancelled before it starts so nothing gets anywhere
+    let cancel = CancelToken::default();
+    cancel.cancel();
+    let mut cancelled = params.clone();
+    for entry in cancelled.entries.iter_mut() {
+        entry.params.cancel = Some(cancel.clone());
+    }
+    let result = portfolio_tiling(&tileset, w, h, &cancelled);
+    assert_eq!(result.bad_cells, w*h);
+
+    let result = portfolio_tiling(&tileset, w, h, &PortfolioParams { time_limit: Some(Duration::ZERO), ..params.clone() });
+    assert_eq!(result.bad_cells, w*h);
+}
@@ -15,14 +15,8 @@ impl<P: Ord + Copy + std::fmt::Debug, V: Copy + Eq + std::hash::Hash+ std::fmt::
         }
     }
 
-    pub fn len(&self) -> usize {
-        self.elems.len()
-    }
-
     fn swap(&mut self, i: usize, j: usize) {
-        let tmp = self.elems[i];
-        self.elems[i] = self.elems[j];
-        self.elems[j] = tmp;
+        self.elems.swap(i, j);
         
         self.value_map.insert(self.elems[j].1, j);
         self.value_map.insert(self.elems[i].1, i);
@@ -30,10 +24,10 @@ impl<P: Ord + Copy + std::fmt::Debug, V: Copy + Eq + std::hash::Hash+ std::fmt::
 
     fn upheap(&mut self, mut i: usize) {
         while i > 0 {
-            let parent = self.elems[i/2];
-            if parent.0 > self.elems[i].0 {
-                self.swap(i, i/2);
-                i /= 2;
+            let parent = (i - 1) / 2;
+            if self.elems[parent].0 > self.elems[i].0 {
+                self.swap(i, parent);
+                i = parent;
             } else {
                 return
             }
@@ -62,7 +56,7 @@ impl<P: Ord + Copy + std::fmt::Debug, V: Copy + Eq + std::hash::Hash+ std::fmt::
             let greater_than_left = self.elems[i].0 > self.elems[i*2+1].0;
             let greater_than_right = self.elems[i].0 > self.elems[i*2+2].0;
 
-            let swap_left = (greater_than_left && greater_than_right && !left_greatest) || (greater_than_left && !greater_than_right);
+            let swap_left = greater_than_left && (!greater_than_right || !left_greatest);
             
             if swap_left {
                 self.swap(i, i*2+1);
@@ -70,7 +64,7 @@ impl<P: Ord + Copy + std::fmt::Debug, V: Copy + Eq + std::hash::Hash+ std::fmt::
                 continue;
             }
 
-            let swap_right = (greater_than_left && greater_than_right && left_greatest) || (greater_than_right && !greater_than_left);
+            let swap_right = greater_than_right && (left_greatest || !greater_than_left);
 
             if swap_right {
                 self.swap(i, i*2+2);
@@ -82,10 +76,10 @@ impl<P: Ord + Copy + std::fmt::Debug, V: Copy + Eq + std::hash::Hash+ std::fmt::
         }
     }
 
-    fn update_priority(&mut self, elem_idx: usize, new_K: P) {
-        let old_K = self.elems[elem_idx].0;
-        self.elems[elem_idx].0 = new_K;
-        if new_K > old_K {
+    fn update_priority(&mut self, elem_idx: usize, new_k: P) {
+        let old_k = self.elems[elem_idx].0;
+        self.elems[elem_idx].0 = new_k;
+        if new_k > old_k {
             self.downheap(elem_idx);
         } else {
             self.upheap(elem_idx);
@@ -109,7 +103,7 @@ impl<P: Ord + Copy + std::fmt::Debug, V: Copy + Eq + std::hash::Hash+ std::fmt::
     }
 
     pub fn remove_min(&mut self) -> Option<V> {
-        if self.elems.len() == 0 {
+        if self.elems.is_empty() {
             return None;
         }
 
@@ -119,7 +113,7 @@ impl<P: Ord + Copy + std::fmt::Debug, V: Copy + Eq + std::hash::Hash+ std::fmt::
         self.downheap(0);
 
         self.value_map.remove(&min.1);
-        return Some(min.1);
+        Some(min.1)
     }
 }
 
@@ -202,4 +196,21 @@ fn pq_test() {
     assert_eq!(pq.remove_min().unwrap(), "asdasd");
     assert_eq!(pq.remove_min().unwrap(), "hhh");
     assert_eq!(pq.remove_min(), None);
-}
\ No newline at end of file
+}
+
+// children of i are 2i+1 and 2i+2, so the parent is (i-1)/2. Going to i/2 instead could swap with
+// a sibling's subtree and leave a smaller key stuck under a bigger one
+#[test]
+fn pq_test_upheap_parent() {
+    let mut pq = PriorityQueue::new();
+    let sets = [(9, 4), (1, 2), (15, 6), (3, 3), (1, 0), (15, 2), (1, 7), (13, 7), (18, 0)];
+    for (k, v) in sets {
+        pq.set(k, v);
+    }
+    let key = |v: i32| sets.iter().rev().find(|s| s.1 == v).unwrap().0;
+    let mut keys = Vec::new();
+    while let Some(v) = pq.remove_min() {
+        keys.push(key(v));
+    }
+    assert_eq!(keys, vec![3, 9, 13, 15, 15, 18]);
+}
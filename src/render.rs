@@ -0,0 +1,72 @@
+use crate::constraint::*;
+use crate::image_output::*;
+use crate::tileset::*;
+
+// border pixel that matches anything. Unset and failed cells are drawn solid so they stand out,
+// they don't take colours from their neighbours or give any
+fn is_wildcard(handle: TileHandle, tile: &TileSpec, i: usize, j: usize) -> bool {
+    if handle == HANDLE_UNSET || handle == HANDLE_FAILED_PLACEMENT {
+        return false;
+    }
+    match tile.constraint {
+        TileConstraint::Pixels(c) => c.mask & border_mask(c.n) & (1 << (j*c.n + i)) != 0,
+        TileConstraint::Sockets(_) => false,
+    }
+}
+
+pub fn render_tiling(tileset: &TileSet, tiling: &[TileHandle], w: usize, h: usize) -> ImageBuffer {
+    let n = tileset.tile_size;
+    let mut out_buf = ImageBuffer::new(w*n, h*n);
+
+    for tile_i in 0..w {
+        for tile_j in 0..h {
+            let handle = tiling[tile_j * w + tile_i];
+            let tile = tileset.get_tilespec(handle);
+            for i in 0..n {
+                for j in 0..n {
+                    let mut colour = tile.px_colour[i+j*n];
+                    // a wildcard border pixel is shared with the neighbour, draw it their colour if they have one
+                    if is_wildcard(handle, &tile, i, j) {
+                        let shared = [
+                            (i == 0 && tile_i > 0, tile_i.wrapping_sub(1), tile_j, n-1, j),
+                            (i == n-1 && tile_i+1 < w, tile_i+1, tile_j, 0, j),
+                            (j == 0 && tile_j > 0, tile_i, tile_j.wrapping_sub(1), i, n-1),
+                            (j == n-1 && tile_j+1 < h, tile_i, tile_j+1, i, 0),
+                        ];
+                        for (ok, ni, nj, ni_px, nj_px) in shared {
+                            if !ok {
+                                continue;
+                            }
+                            let neighbour_handle = tiling[nj * w + ni];
+                            if neighbour_handle == HANDLE_UNSET || neighbour_handle == HANDLE_FAILED_PLACEMENT {
+                                continue;
+                            }
+                            let neighbour = tileset.get_tilespec(neighbour_handle);
+                            if !is_wildcard(neighbour_handle, &neighbour, ni_px, nj_px) {
+                                colour = neighbour.px_colour[ni_px + nj_px*n];
+                                break;
+                            }
+                        }
+                    }
+                    out_buf.set_px(tile_i * n + i, tile_j * n + j, colour);
+                }
+            }
+        }
+    }
+
+    out_buf
+}
+
+#[test]
+fn test_render_markers() {
+    let tileset = load_tileset("test_tilesets/roads.png", 3).unwrap();
+    let n = tileset.tile_size;
+    let tiling = [HANDLE_FAILED_PLACEMENT, 0, 0, HANDLE_UNSET];
+    let image = render_tiling(&tileset, &tiling, 2, 2);
+    for j in 0..n {
+        for i in 0..n {
+            assert_eq!(image.get_px(i, j), (0, 0, 0));
+            assert_eq!(image.get_px(n + i, n + j), (255, 0, 255));
+        }
+    }
+}
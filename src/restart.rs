@@ -0,0 +1,150 @@
+use std::time::Instant;
+
+use crate::krand::*;
+use crate::greedy::*;
+use crate::tileset::*;
+
+// how many steps each run of the greedy solver gets before it's abandoned for a fresh seed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RestartSchedule {
+    // unit times 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ... Within a constant factor of the best fixed
+    // cutoff without having to know what that is
+    Luby { unit: usize },
+    // first, then each run factor times longer than the last
+    Geometric { first: usize, factor: f32 },
+}
+
+impl RestartSchedule {
+    // steps for run k, counting from 0
+    pub fn budget(&self, k: usize) -> usize {
+        match *self {
+            RestartSchedule::Luby { unit } => unit * luby(k + 1),
+            RestartSchedule::Geometric { first, factor } => (first as f32 * factor.powi(k as i32)) as usize,
+        }
+    }
+}
+
+// the i-th term of the Luby sequence, from 1
+pub fn luby(i: usize) -> usize {
+    let mut i = i;
+    loop {
+        // smallest k with i <= 2^k - 1
+        let mut k = 1;
+        while (1 << k) - 1 < i {
+            k += 1;
+        }
+        if i == (1 << k) - 1 {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RestartParams {
+    pub schedule: RestartSchedule,
+    // steps over all the runs together
+    pub total_steps: usize,
+    // greedy.time_limit is for all the runs together too
+    pub greedy: GreedyParams,
+    // print a line about the runs at the end
+    pub verbose: bool,
+}
+
+impl Default for RestartParams {
+    fn default() -> RestartParams {
+        RestartParams {
+            schedule: RestartSchedule::Luby { unit: 4000 },
+            total_steps: 200_000,
+            greedy: GreedyParams::default(),
+            verbose: false,
+        }
+    }
+}
+
+pub struct BestRun {
+    pub tiles: Vec<TileHandle>,
+    // failed and never placed cells, what the runs are compared on
+    pub bad_cells: usize,
+    // greedy_run with this seed and params gives back the same tiles, unless the run was stopped by
+    // the clock or cancelled. Those aren't in params since they'd stop it somewhere else next time
+    pub seed: u32,
+    pub params: GreedyParams,
+    pub runs: usize,
+    // over all the runs
+    pub steps: usize,
+}
+
+// the seed for run k. Run 0 gets seed itself so a budget big enough for one run changes nothing
+pub fn restart_seed(seed: u32, k: usize) -> u32 {
+    if k == 0 { seed } else { khash(seed ^ khash(k as u32)) }
+}
+
+// runs the greedy solver over and over with new seeds, each run cut off after the schedule's
+// budget for it, until one fills the map or the total runs out. Some seeds go straight through a
+// tileset that others fill with failed squares, and a run that's getting nowhere is usually better
+// off starting again than grinding on. Keeps whichever run left the fewest bad cells
+pub fn restart_tiling(tileset: &TileSet, w: usize, h: usize, seed: u32, params: &RestartParams) -> BestRun {
+    let deadline = params.greedy.time_limit.map(|limit| Instant::now() + limit);
+    let mut best: Option<BestRun> = None;
+    let mut used = 0;
+    let mut k = 0;
+    while used < params.total_steps {
+        let max_steps = params.schedule.budget(k).max(1).min(params.total_steps - used);
+        let run_seed = restart_seed(seed, k);
+        // each run only gets what's left of the time
+        let time_limit = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let run_params = GreedyParams { max_steps: Some(max_steps), time_limit: None, cancel: None, ..params.greedy.clone() };
+        let run = greedy_run(tileset, w, h, run_seed, &GreedyParams { time_limit, cancel: params.greedy.cancel.clone(), ..run_params.clone() });
+        used += run.steps;
+        k += 1;
+
+        let bad_cells = run.bad_cells();
+        let succeeded = run.succeeded();
+        if best.as_ref().is_none_or(|b| bad_cells < b.bad_cells) {
+            best = Some(BestRun { tiles: run.tiles, bad_cells, seed: run_seed, params: run_params, runs: 0, steps: 0 });
+        }
+        // a run that couldn't take a single step won't be the last to do that
+        let out_of_time = run.stopped == Some(StopReason::Time) || run.steps == 0;
+        if succeeded || run.stopped == Some(StopReason::Cancelled) || out_of_time {
+            break;
+        }
+    }
+
+    let mut best = best.unwrap_or_else(|| BestRun { tiles: vec![HANDLE_UNSET; w*h], bad_cells: w*h, seed, params: params.greedy.clone(), runs: 0, steps: 0 });
+    best.runs = k;
+    best.steps = used;
+    if params.verbose {
+        println!("restarts -- runs: {} steps: {} best seed: {} (max steps {:?}) bad cells: {}", k, used, best.seed, best.params.max_steps, best.bad_cells);
+    }
+    best
+}
+
+#[test]
+fn test_restart_tiling() {
+    let first: Vec<usize> = (1..=15).map(luby).collect();
+    assert_eq!(first, vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    let geometric = RestartSchedule::Geometric { first: 100, factor: 2.0 };
+    assert_eq!((0..4).map(|k| geometric.budget(k)).collect::<Vec<_>>(), vec![100, 200, 400, 800]);
+
+    let tileset = load_tileset("test_tilesets/testvillage.png", 3).unwrap();
+    let (w, h) = (20, 20);
+    let params = RestartParams { schedule: RestartSchedule::Luby { unit: 300 }, total_steps: 20000, ..RestartParams::default() };
+    let best = restart_tiling(&tileset, w, h, 69, &params);
+    assert_eq!(best.bad_cells, 0);
+
+    // the winning seed and params give the same map again
+    let again = greedy_run(&tileset, w, h, best.seed, &best.params);
+    assert_eq!(again.tiles, best.tiles);
+
+    // runs too short to finish anything still hand back the one that got furthest
+    let params = RestartParams { schedule: RestartSchedule::Luby { unit: 10 }, total_steps: 100, ..RestartParams::default() };
+    let best = restart_tiling(&tileset, w, h, 69, &params);
+    assert!(best.runs > 1 && best.bad_cells > 0);
+    assert_eq!(best.steps, 100);
+
+    // the time limit covers all the runs, and with none left it gives up rather than spinning
+    let greedy = GreedyParams { time_limit: Some(std::time::Duration::ZERO), ..GreedyParams::default() };
+    let best = restart_tiling(&tileset, w, h, 69, &RestartParams { greedy, ..RestartParams::default() });
+    assert_eq!((best.runs, best.bad_cells), (1, w*h));
+}
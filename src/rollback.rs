@@ -0,0 +1,163 @@
+use serde::Deserialize;
+
+// which cells around a failed one get cleared
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RollbackShape {
+    // the (2r+1) square less its corners
+    #[default]
+    Square,
+    // everything within r steps, |di| + |dj| <= r
+    Diamond,
+    // the failed cell and the tiles that are actually holding it back: the placed tiles next to it,
+    // then the ones next to those and so on out to r steps, never crossing an empty cell
+    Constraining,
+    // only the placements that actually took something out of the failed cell's domain, through any
+    // of its sides, then the ones that did the same to those when they were placed, r deep
+    Provenance,
+}
+
+// how the greedy solver backs out of a cell with nothing left that fits. Every time a cell gets
+// cleared its reroll count goes up, and the schedule maps that to how far to roll back. In the
+// manifest:
+//
+// [rollback]
+// schedule = [[100, 1], [1000, 2]]  # under 100 rerolls radius 1, then under 1000 radius 2
+// shape = "square"                  # square, diamond, constraining, provenance
+//
+// past the end of the schedule the cell is given up on and left failed. For the shapes that follow
+// tiles rather than distance the radius is how many steps deep to go
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields, default)]
+pub struct RollbackPolicy {
+    pub schedule: Vec<(u32, usize)>,
+    pub shape: RollbackShape,
+}
+
+impl Default for RollbackPolicy {
+    fn default() -> RollbackPolicy {
+        RollbackPolicy {
+            schedule: vec![(100, 1), (1000, 2)],
+            shape: RollbackShape::Square,
+        }
+    }
+}
+
+impl RollbackPolicy {
+    // None means give up
+    pub fn radius(&self, rerolls: u32) -> Option<usize> {
+        self.schedule.iter().find(|&&(below, _)| rerolls < below).map(|&(_, r)| r)
+    }
+
+    // the cells to clear around (i, j), which is always first. placed says whether a cell has a tile,
+    // contributors which placements narrowed a cell down, for Provenance
+    pub fn cells(&self, w: usize, h: usize, (i, j): (usize, usize), r: usize,
+            placed: impl Fn(usize, usize) -> bool, contributors: impl Fn(usize, usize) -> Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        let r = r as i32;
+        // (ci, cj) moved by (oi, oj), if that's still on the grid
+        let offset = |(ci, cj): (usize, usize), oi: i32, oj: i32| {
+            let (ni, nj) = (ci as i32 + oi, cj as i32 + oj);
+            (ni >= 0 && nj >= 0 && ni < w as i32 && nj < h as i32).then_some((ni as usize, nj as usize))
+        };
+        let mut cells = vec![(i, j)];
+        match self.shape {
+            RollbackShape::Square | RollbackShape::Diamond => {
+                for oj in -r..=r {
+                    for oi in -r..=r {
+                        let inside = match self.shape {
+                            RollbackShape::Square => !(oi.abs() == r && oj.abs() == r),
+                            _ => oi.abs() + oj.abs() <= r,
+                        };
+                        if inside && (oi, oj) != (0, 0) {
+                            cells.extend(offset((i, j), oi, oj));
+                        }
+                    }
+                }
+            },
+            RollbackShape::Constraining | RollbackShape::Provenance => {
+                // breadth first out from (i, j), a step is to a placed neighbour or a contributor
+                let mut frontier = vec![(i, j)];
+                for _ in 0..r {
+                    let mut next = Vec::new();
+                    for &(ci, cj) in &frontier {
+                        let steps: Vec<(usize, usize)> = match self.shape {
+                            RollbackShape::Constraining => [(0, -1), (1, 0), (0, 1), (-1, 0)].into_iter()
+                                .filter_map(|(oi, oj)| offset((ci, cj), oi, oj))
+                                .collect(),
+                            _ => contributors(ci, cj),
+                        };
+                        for (ni, nj) in steps {
+                            if placed(ni, nj) && !cells.contains(&(ni, nj)) {
+                                cells.push((ni, nj));
+                                next.push((ni, nj));
+                            }
+                        }
+                    }
+                    frontier = next;
+                }
+            },
+        }
+        cells
+    }
+}
+
+#[test]
+fn test_rollback_policy() {
+    let policy = RollbackPolicy::default();
+    assert_eq!(policy.radius(0), Some(1));
+    assert_eq!(policy.radius(100), Some(2));
+    assert_eq!(policy.radius(1000), None);
+
+    let all = |_: usize, _: usize| true;
+    let none = |_: usize, _: usize| Vec::new();
+    let mut square = policy.cells(9, 9, (4, 4), 2, all, none);
+    assert_eq!(square[0], (4, 4));
+    assert_eq!(square.len(), 25 - 4);
+    square.sort();
+    let mut diamond = RollbackPolicy { shape: RollbackShape::Diamond, ..RollbackPolicy::default() }.cells(9, 9, (4, 4), 2, all, none);
+    assert_eq!(diamond.len(), 13);
+    diamond.sort();
+    assert!(diamond.iter().all(|c| square.binary_search(c).is_ok()));
+    // cut off by the edge
+    assert_eq!(policy.cells(9, 9, (0, 0), 1, all, none).len(), 3);
+
+    // placed cells in a chain back to (4, 4). (6, 3) is placed too but only gets there across empty cells
+    let placed = |i: usize, j: usize| [(4, 3), (4, 2), (5, 4), (6, 3)].contains(&(i, j));
+    let constraining = RollbackPolicy { shape: RollbackShape::Constraining, ..RollbackPolicy::default() };
+    assert_eq!(constraining.cells(9, 9, (4, 4), 1, placed, none), vec![(4, 4), (4, 3), (5, 4)]);
+    assert_eq!(constraining.cells(9, 9, (4, 4), 2, placed, none), vec![(4, 4), (4, 3), (5, 4), (4, 2)]);
+
+    // (6, 3) narrowed (4, 4) from afar and (4, 2) narrowed (6, 3). (4, 3) is right next to it but
+    // didn't take anything out. (1, 1) did once but has been cleared since
+    let contributors = |i: usize, j: usize| match (i, j) {
+        (4, 4) => vec![(6, 3), (1, 1)],
+        (6, 3) => vec![(4, 2)],
+        _ => Vec::new(),
+    };
+    let provenance = RollbackPolicy { shape: RollbackShape::Provenance, ..RollbackPolicy::default() };
+    assert_eq!(provenance.cells(9, 9, (4, 4), 1, placed, contributors), vec![(4, 4), (6, 3)]);
+    assert_eq!(provenance.cells(9, 9, (4, 4), 2, placed, contributors), vec![(4, 4), (6, 3), (4, 2)]);
+    assert_eq!(provenance.cells(9, 9, (4, 4), 0, placed, contributors), vec![(4, 4)]);
+
+    let manifest = crate::manifest::Manifest::parse("[rollback]\nschedule = [[50, 1], [60, 3]]\nshape = \"constraining\"").unwrap();
+    let policy = manifest.rollback.unwrap();
+    assert_eq!(policy.schedule, vec![(50, 1), (60, 3)]);
+    assert_eq!(policy.radius(55), Some(3));
+    assert_eq!(policy.shape, RollbackShape::Constraining);
+    // leaving things out keeps the defaults
+    let manifest = crate::manifest::Manifest::parse("[rollback]\nshape = \"diamond\"").unwrap();
+    assert_eq!(manifest.rollback.unwrap().schedule, RollbackPolicy::default().schedule);
+
+    // the schedule in roads.toml gets it there in fewer steps than the default. Not on every seed,
+    // so over a few of them
+    let roads = crate::tileset::load_tileset("test_tilesets/roads.png", 3).unwrap();
+    assert_eq!(roads.rollback, RollbackPolicy { schedule: vec![(10, 2), (1000, 3)], shape: RollbackShape::Square });
+    let steps = |params: &crate::greedy::GreedyParams| (0..12).map(|seed| {
+        let run = crate::greedy::greedy_run(&roads, 40, 40, seed, params);
+        assert!(!run.tiles.contains(&crate::tileset::HANDLE_FAILED_PLACEMENT));
+        run.steps
+    }).sum::<usize>();
+    let ours = steps(&crate::greedy::GreedyParams::default());
+    let default = steps(&crate::greedy::GreedyParams { rollback: Some(RollbackPolicy::default()), ..Default::default() });
+    assert!(ours < default, "{} {}", ours, default);
+}
@@ -0,0 +1,211 @@
+use std::path::Path;
+
+use crate::grid::*;
+use crate::tileset::*;
+
+// explicit adjacency rules for the stuff you cant say with border colours. Sidecar next to the
+// tileset png with .rules on the end instead, one rule per line:
+//
+// # huts need some space
+// mode combine            # or replace, default combine
+// deny hut* any hut*
+// allow road_end east bridge
+//
+// "allow a east b" means a may sit east of b. Names are tile names from the manifest (or tile_x_y),
+// a trailing * matches any name starting with the rest, handy for the _rot90 etc copies.
+// In combine mode a pair of neighbours is ok if the borders fit or an allow rule says so, and no
+// deny rule forbids it. In replace mode the borders are ignored and only allow rules let tiles touch
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RuleMode {
+    Combine,
+    Replace,
+}
+
+#[derive(Debug)]
+pub enum RulesError {
+    Io(std::io::Error),
+    Syntax { line: usize, msg: String },
+    // a rule that doesn't name any tile in the tileset, probably a typo
+    NoMatch { line: usize, pattern: String },
+}
+
+impl std::fmt::Display for RulesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RulesError::Io(e) => write!(f, "couldn't read rules: {}", e),
+            RulesError::Syntax { line, msg } => write!(f, "rules line {}: {}", line, msg),
+            RulesError::NoMatch { line, pattern } => write!(f, "rules line {}: {} doesn't match any tile", line, pattern),
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+// compiled against a tileset. allow and deny are 4 * n_tiles * n_tiles, see idx
+pub struct AdjacencyRules {
+    pub mode: RuleMode,
+    n_tiles: usize,
+    allow: Vec<bool>,
+    deny: Vec<bool>,
+}
+
+impl AdjacencyRules {
+    // b sits in direction dir of a
+    fn idx(&self, a: TileHandle, b: TileHandle, dir: Dir) -> usize {
+        (dir.idx() * self.n_tiles + a as usize) * self.n_tiles + b as usize
+    }
+
+    pub fn allowed(&self, a: TileHandle, b: TileHandle, dir: Dir) -> bool {
+        self.allow[self.idx(a, b, dir)]
+    }
+
+    pub fn denied(&self, a: TileHandle, b: TileHandle, dir: Dir) -> bool {
+        self.deny[self.idx(a, b, dir)]
+    }
+
+    // borders_fit is only asked for in combine mode
+    pub fn pair_ok(&self, a: TileHandle, b: TileHandle, dir: Dir, borders_fit: impl FnOnce() -> bool) -> bool {
+        if self.denied(a, b, dir) {
+            return false;
+        }
+        match self.mode {
+            RuleMode::Combine => self.allowed(a, b, dir) || borders_fit(),
+            RuleMode::Replace => self.allowed(a, b, dir),
+        }
+    }
+
+    fn set(table: &mut [bool], n_tiles: usize, a: TileHandle, b: TileHandle, dir: Dir) {
+        table[(dir.idx() * n_tiles + a as usize) * n_tiles + b as usize] = true;
+        table[(dir.opposite().idx() * n_tiles + b as usize) * n_tiles + a as usize] = true;
+    }
+
+    pub fn parse(text: &str, tileset: &TileSet) -> Result<AdjacencyRules, RulesError> {
+        let n_tiles = tileset.vec.len();
+        let mut rules = AdjacencyRules {
+            mode: RuleMode::Combine,
+            n_tiles,
+            allow: vec![false; 4 * n_tiles * n_tiles],
+            deny: vec![false; 4 * n_tiles * n_tiles],
+        };
+
+        for (line_idx, line) in text.lines().enumerate() {
+            let line_no = line_idx + 1;
+            let line = line.split('#').next().unwrap().trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let syntax = |msg: &str| RulesError::Syntax { line: line_no, msg: msg.to_string() };
+            match words.as_slice() {
+                [] => {},
+                ["mode", "combine"] => rules.mode = RuleMode::Combine,
+                ["mode", "replace"] => rules.mode = RuleMode::Replace,
+                ["mode", ..] => return Err(syntax("mode is combine or replace")),
+                [kind @ ("allow" | "deny"), a, dir, b] => {
+                    let dirs: Vec<Dir> = match *dir {
+                        "north" => vec![Dir::North],
+                        "east" => vec![Dir::East],
+                        "south" => vec![Dir::South],
+                        "west" => vec![Dir::West],
+                        "any" => DIRS.to_vec(),
+                        _ => return Err(syntax("direction is north, east, south, west or any")),
+                    };
+                    let a_tiles = tileset.tiles_matching(a);
+                    let b_tiles = tileset.tiles_matching(b);
+                    for (pattern, tiles) in [(a, &a_tiles), (b, &b_tiles)] {
+                        if tiles.is_empty() {
+                            return Err(RulesError::NoMatch { line: line_no, pattern: pattern.to_string() });
+                        }
+                    }
+                    let table = if *kind == "allow" { &mut rules.allow } else { &mut rules.deny };
+                    for &a in a_tiles.iter() {
+                        for &b in b_tiles.iter() {
+                            for &dir in dirs.iter() {
+                                // a sits in direction dir of b
+                                AdjacencyRules::set(table, n_tiles, b, a, dir);
+                            }
+                        }
+                    }
+                },
+                _ => return Err(syntax("expected allow/deny <tile> <dir> <tile> or mode <combine|replace>")),
+            }
+        }
+
+        Ok(rules)
+    }
+
+    // tileset.png -> tileset.rules, None if there isn't one
+    pub fn load_sidecar(png_path: &str, tileset: &TileSet) -> Result<Option<AdjacencyRules>, RulesError> {
+        let path = Path::new(png_path).with_extension("rules");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(&path).map_err(RulesError::Io)?;
+        AdjacencyRules::parse(&text, tileset).map(Some)
+    }
+}
+
+#[test]
+fn test_rules() {
+    use crate::image_output::*;
+    use crate::manifest::*;
+
+    // grass and a hut on grass, everything fits everything by the borders
+    let n = 3;
+    let mut image = ImageBuffer::new(2*(n+1), n+1);
+    for y in 0..n {
+        for x in 0..n {
+            image.set_px(x, y, (0, 255, 0));
+            image.set_px(n+1 + x, y, if x == 1 && y == 1 { (128, 64, 0) } else { (0, 255, 0) });
+        }
+    }
+    image.set_px(0, n, (1, 0, 0));
+    image.set_px(n+1, n, (1, 0, 0));
+    let manifest = Manifest::parse(r#"
+        [[tile]]
+        x = 0
+        y = 0
+        name = "grass"
+        [[tile]]
+        x = 1
+        y = 0
+        name = "hut"
+    "#).unwrap();
+    let mut tileset = make_tileset(&image, n, Some(&manifest)).unwrap();
+
+    let rules = AdjacencyRules::parse("deny hut any hut # no neighbours\nallow grass east hut", &tileset).unwrap();
+    assert!(rules.denied(1, 1, Dir::West));
+    assert!(!rules.denied(0, 1, Dir::West));
+    // grass may sit east of a hut, so the hut can sit west of grass
+    assert!(rules.allowed(1, 0, Dir::East));
+    assert!(rules.allowed(0, 1, Dir::West));
+    assert!(!rules.allowed(0, 1, Dir::East));
+
+    assert!(matches!(AdjacencyRules::parse("deny shed any hut", &tileset), Err(RulesError::NoMatch { line: 1, .. })));
+    assert!(matches!(AdjacencyRules::parse("\nallow hut up hut", &tileset), Err(RulesError::Syntax { line: 2, .. })));
+
+    tileset.set_rules(Some(rules));
+    let (w, h) = (20, 20);
+    let tiling = crate::greedy_run(&tileset, w, h, 69, &crate::GreedyParams::default()).tiles;
+    assert!(tiling.iter().all(|&t| t != HANDLE_FAILED_PLACEMENT));
+    assert!(tiling.contains(&1));
+    for j in 0..h {
+        for i in 0..w {
+            if tiling[j*w + i] == 1 {
+                assert!(i + 1 == w || tiling[j*w + i + 1] != 1);
+                assert!(j + 1 == h || tiling[(j+1)*w + i] != 1);
+            }
+        }
+    }
+
+    // replace mode ignores that the hut borders fit each other
+    tileset.set_rules(Some(AdjacencyRules::parse("mode replace\nallow grass any grass\nallow hut any grass", &tileset).unwrap()));
+    let tiling = crate::greedy_run(&tileset, w, h, 70, &crate::GreedyParams::default()).tiles;
+    assert!(tiling.iter().all(|&t| t != HANDLE_FAILED_PLACEMENT));
+    assert!(tiling.contains(&1));
+    for j in 0..h {
+        for i in 0..w {
+            if tiling[j*w + i] == 1 {
+                assert!(i + 1 == w || tiling[j*w + i + 1] == 0);
+                assert!(j + 1 == h || tiling[(j+1)*w + i] == 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::time::{Duration, Instant};
+
+use crate::anneal::*;
+use crate::backtrack::*;
+use crate::genetic::*;
+use crate::greedy::*;
+use crate::grid::*;
+use crate::local::*;
+use crate::portfolio::*;
+use crate::restart::*;
+use crate::tabu::*;
+use crate::tileset::*;
+
+// what every solver gets asked, anything particular to one solver lives on the solver itself
+#[derive(Clone, Debug)]
+pub struct SolverConfig {
+    pub w: usize,
+    pub h: usize,
+    pub seed: u32,
+    // for the whole solve, whichever solver it is. Solvers with limits of their own go with
+    // whichever runs out first
+    pub budget: Budget,
+}
+
+#[derive(Clone, Debug)]
+pub struct SolverStats {
+    pub elapsed: Duration,
+    // mismatched borders between placed tiles
+    pub violations: usize,
+    // cells given up on, HANDLE_FAILED_PLACEMENT
+    pub failed: usize,
+    // cells never got to, HANDLE_UNSET
+    pub unset: usize,
+}
+
+pub struct SolverResult {
+    pub tiles: Vec<TileHandle>,
+    pub stats: SolverStats,
+    // every cell has a tile and all the borders fit
+    pub success: bool,
+}
+
+impl SolverResult {
+    pub fn new(tileset: &TileSet, config: &SolverConfig, tiles: Vec<TileHandle>, elapsed: Duration) -> SolverResult {
+        let placed = |t: TileHandle| t != HANDLE_UNSET && t != HANDLE_FAILED_PLACEMENT;
+        let (w, h) = (config.w, config.h);
+        let violations = (0..w*h).filter(|&cell| placed(tiles[cell])).map(|cell| {
+            grid_neighbours(w, h, cell)
+                .filter(|&(dir, neighbour)| (dir == Dir::East || dir == Dir::South) &&
+                    placed(tiles[neighbour]) && !tileset.index.fits(tiles[cell], dir).contains(tiles[neighbour]))
+                .count()
+        }).sum();
+        let failed = tiles.iter().filter(|&&t| t == HANDLE_FAILED_PLACEMENT).count();
+        let unset = tiles.iter().filter(|&&t| t == HANDLE_UNSET).count();
+        SolverResult {
+            tiles,
+            stats: SolverStats { elapsed, violations, failed, unset },
+            success: violations == 0 && failed == 0 && unset == 0,
+        }
+    }
+}
+
+// one way of filling a map. Implementations only have to say how to get the tiles, solve times it
+// and works out the stats the same way for all of them so they can be compared. Every one of them
+// checks config.budget before each step of its main loop and hands back what it has when it's out
+pub trait Solver {
+    fn name(&self) -> &str;
+
+    fn tiles(&self, tileset: &TileSet, config: &SolverConfig) -> Vec<TileHandle>;
+
+    fn solve(&self, tileset: &TileSet, config: &SolverConfig) -> SolverResult {
+        let start = Instant::now();
+        let tiles = self.tiles(tileset, config);
+        SolverResult::new(tileset, config, tiles, start.elapsed())
+    }
+}
+
+pub struct GreedySolver {
+    pub params: GreedyParams,
+}
+
+impl Solver for GreedySolver {
+    fn name(&self) -> &str {
+        match self.params.heuristic {
+            CellHeuristic::Count => "greedy",
+            CellHeuristic::Entropy => "greedy_entropy",
+        }
+    }
+
+    fn tiles(&self, tileset: &TileSet, config: &SolverConfig) -> Vec<TileHandle> {
+        greedy_run(tileset, config.w, config.h, config.seed, &config.budget.limit(&self.params)).tiles
+    }
+}
+
+pub struct RestartSolver {
+    pub params: RestartParams,
+}
+
+impl Solver for RestartSolver {
+    fn name(&self) -> &str {
+        "greedy_restarts"
+    }
+
+    fn tiles(&self, tileset: &TileSet, config: &SolverConfig) -> Vec<TileHandle> {
+        let params = RestartParams {
+            total_steps: config.budget.max_steps.map_or(self.params.total_steps, |m| m.min(self.params.total_steps)),
+            // restart_tiling sets max_steps for each run itself
+            greedy: config.budget.limit(&self.params.greedy),
+            ..self.params.clone()
+        };
+        restart_tiling(tileset, config.w, config.h, config.seed, &params).tiles
+    }
+}
+
+// PortfolioParams::mixed from config.seed
+pub struct PortfolioSolver {
+    pub seeds: usize,
+    pub threads: usize,
+}
+
+impl Solver for PortfolioSolver {
+    fn name(&self) -> &str {
+        "portfolio"
+    }
+
+    fn tiles(&self, tileset: &TileSet, config: &SolverConfig) -> Vec<TileHandle> {
+        // every entry gets the whole step budget, they run side by side
+        let mut params = PortfolioParams { threads: self.threads, time_limit: config.budget.time_limit, ..PortfolioParams::mixed(config.seed, self.seeds) };
+        for entry in params.entries.iter_mut() {
+            entry.params = config.budget.limit(&entry.params);
+        }
+        portfolio_tiling(tileset, config.w, config.h, &params).tiles
+    }
+}
+
+pub struct BacktrackSolver {
+    pub max_steps: usize,
+}
+
+impl Solver for BacktrackSolver {
+    fn name(&self) -> &str {
+        "backtrack"
+    }
+
+    // no tiling at all comes back as nothing placed
+    fn tiles(&self, tileset: &TileSet, config: &SolverConfig) -> Vec<TileHandle> {
+        match backtrack_tiling(tileset, config.w, config.h, config.seed, &config.budget.with_steps(self.max_steps)) {
+            SearchOutcome::Solved(tiles) | SearchOutcome::OutOfSteps(tiles) => tiles,
+            SearchOutcome::Unsatisfiable => vec![HANDLE_UNSET; config.w*config.h],
+        }
+    }
+}
+
+pub struct MinConflictsSolver {
+    pub max_steps: usize,
+}
+
+impl Solver for MinConflictsSolver {
+    fn name(&self) -> &str {
+        "min_conflicts"
+    }
+
+    fn tiles(&self, tileset: &TileSet, config: &SolverConfig) -> Vec<TileHandle> {
+        min_conflicts_tiling_steps(tileset, config.w, config.h, config.seed, &config.budget.with_steps(self.max_steps))
+    }
+}
+
+pub struct AnnealSolver {
+    pub params: AnnealParams,
+}
+
+impl Solver for AnnealSolver {
+    fn name(&self) -> &str {
+        "anneal"
+    }
+
+    fn tiles(&self, tileset: &TileSet, config: &SolverConfig) -> Vec<TileHandle> {
+        anneal_tiling_with(tileset, config.w, config.h, config.seed, &self.params, &config.budget)
+    }
+}
+
+pub struct TabuSolver {
+    pub params: TabuParams,
+}
+
+impl Solver for TabuSolver {
+    fn name(&self) -> &str {
+        "tabu"
+    }
+
+    fn tiles(&self, tileset: &TileSet, config: &SolverConfig) -> Vec<TileHandle> {
+        tabu_tiling_with(tileset, config.w, config.h, config.seed, &self.params, &config.budget)
+    }
+}
+
+pub struct GeneticSolver {
+    pub params: GeneticParams,
+}
+
+impl Solver for GeneticSolver {
+    fn name(&self) -> &str {
+        "genetic"
+    }
+
+    fn tiles(&self, tileset: &TileSet, config: &SolverConfig) -> Vec<TileHandle> {
+        genetic_tiling_with(tileset, config.w, config.h, config.seed, &self.params, &config.budget)
+    }
+}
+
+pub const SOLVER_NAMES: [&str; 9] = ["greedy", "greedy_entropy", "greedy_restarts", "portfolio", "backtrack", "min_conflicts", "anneal", "tabu", "genetic"];
+
+// any of SOLVER_NAMES with its default settings
+pub fn solver_by_name(name: &str) -> Option<Box<dyn Solver>> {
+    let solver: Box<dyn Solver> = match name {
+        "greedy" => Box::new(GreedySolver { params: GreedyParams::default() }),
+        "greedy_entropy" => Box::new(GreedySolver {
+            params: GreedyParams { heuristic: CellHeuristic::Entropy, ..GreedyParams::default() },
+        }),
+        "greedy_restarts" => Box::new(RestartSolver { params: RestartParams::default() }),
+        "portfolio" => Box::new(PortfolioSolver { seeds: 2, threads: 0 }),
+        "backtrack" => Box::new(BacktrackSolver { max_steps: 100_000 }),
+        "min_conflicts" => Box::new(MinConflictsSolver { max_steps: MIN_CONFLICTS_STEPS }),
+        "anneal" => Box::new(AnnealSolver { params: AnnealParams::default() }),
+        "tabu" => Box::new(TabuSolver { params: TabuParams::default() }),
+        "genetic" => Box::new(GeneticSolver { params: GeneticParams::default() }),
+        _ => return None,
+    };
+    Some(solver)
+}
+
+#[test]
+fn test_solver_by_name() {
+    for name in SOLVER_NAMES {
+        assert_eq!(solver_by_name(name).unwrap().name(), name);
+    }
+    assert!(solver_by_name("quantum").is_none());
+
+    let tileset = load_tileset("test_tilesets/roads.png", 3).unwrap();
+    let config = SolverConfig { w: 12, h: 10, seed: 69, budget: Budget::default() };
+    let result = solver_by_name("min_conflicts").unwrap().solve(&tileset, &config);
+    assert!(result.success);
+    assert_eq!(result.tiles.len(), 120);
+    assert_eq!((result.stats.violations, result.stats.failed, result.stats.unset), (0, 0, 0));
+
+    // a partial map counts what's missing and only the borders between placed tiles
+    let mut tiles = result.tiles;
+    tiles[0] = HANDLE_UNSET;
+    tiles[1] = HANDLE_FAILED_PLACEMENT;
+    let partial = SolverResult::new(&tileset, &config, tiles, Duration::ZERO);
+    assert!(!partial.success);
+    assert_eq!((partial.stats.violations, partial.stats.failed, partial.stats.unset), (0, 1, 1));
+
+    // every solver stops when the budget's gone, whatever limits of its own it has. These are all
+    // spent before the first step so nothing gets far enough to succeed
+    let cancel = CancelToken::default();
+    cancel.cancel();
+    let budgets = [
+        Budget::default().with_steps(0),
+        Budget { time_limit: Some(Duration::ZERO), ..Budget::default() },
+        Budget { cancel: Some(cancel), ..Budget::default() },
+    ];
+    for budget in budgets {
+        let config = SolverConfig { budget, ..config.clone() };
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&tileset, &config);
+            assert!(!result.success, "{} {:?}", name, config.budget);
+        }
+        let result = BacktrackSolver { max_steps: 100_000 }.solve(&tileset, &config);
+        assert_eq!(result.stats.unset, 12*10);
+    }
+}
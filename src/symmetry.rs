@@ -0,0 +1,137 @@
+use crate::constraint::*;
+
+pub type TilePixels = [(u8, u8, u8); MAX_TILE_PX];
+
+// which extra copies of a tile to generate
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    Rotate,     // 90, 180, 270
+    FlipLR,     // mirror left to right
+    FlipUD,     // mirror top to bottom
+    FlipBoth,   // both mirrors and the 180 rotation you get from doing both
+    All,        // the whole dihedral group, 4 rotations of the tile and of its mirror image
+}
+
+impl Symmetry {
+    // from the control pixel at (n, 0)
+    pub fn from_control_px(px: (u8, u8, u8)) -> Symmetry {
+        match px {
+            (0x00, 0x00, 0xFF) => Symmetry::Rotate,
+            (0x00, 0xFF, 0x00) => Symmetry::FlipLR,
+            (0xFF, 0xFF, 0x00) => Symmetry::FlipUD,
+            (0x00, 0xFF, 0xFF) => Symmetry::FlipBoth,
+            (0xFF, 0x00, 0xFF) => Symmetry::All,
+            _ => Symmetry::None,
+        }
+    }
+}
+
+// rotates the n x n tile in the first n*n entries
+pub fn rot_tile(n: usize, pixel_data: TilePixels) -> TilePixels {
+    let mut output_data = [(0, 0, 0); MAX_TILE_PX];
+
+    for i in 0..n {
+        for j in 0..n {
+            output_data[i*n + j] = pixel_data[(n-1-j)*n + i];
+        }
+    }
+
+    output_data
+}
+
+pub fn flip_lr_tile(n: usize, pixel_data: TilePixels) -> TilePixels {
+    let mut output_data = [(0, 0, 0); MAX_TILE_PX];
+
+    for i in 0..n {
+        for j in 0..n {
+            output_data[i*n + j] = pixel_data[i*n + (n-1-j)];
+        }
+    }
+
+    output_data
+}
+
+// mirror left to right first if asked, then rotate clockwise rot times
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Transform {
+    pub mirror: bool,
+    pub rot: usize,
+}
+
+// the transforms a symmetry asks for, starting with the identity, labelled with how they're made
+pub fn symmetry_transforms(symmetry: Symmetry) -> Vec<(&'static str, Transform)> {
+    let t = |mirror, rot| Transform { mirror, rot };
+    match symmetry {
+        Symmetry::None => vec![("", t(false, 0))],
+        Symmetry::Rotate => vec![("", t(false, 0)), ("rot90", t(false, 1)), ("rot180", t(false, 2)), ("rot270", t(false, 3))],
+        Symmetry::FlipLR => vec![("", t(false, 0)), ("flip_lr", t(true, 0))],
+        Symmetry::FlipUD => vec![("", t(false, 0)), ("flip_ud", t(true, 2))],
+        Symmetry::FlipBoth => vec![("", t(false, 0)), ("flip_lr", t(true, 0)), ("flip_ud", t(true, 2)), ("rot180", t(false, 2))],
+        Symmetry::All => vec![("", t(false, 0)), ("rot90", t(false, 1)), ("rot180", t(false, 2)), ("rot270", t(false, 3)),
+            ("flip_lr", t(true, 0)), ("flip_lr_rot90", t(true, 1)), ("flip_lr_rot180", t(true, 2)), ("flip_lr_rot270", t(true, 3))],
+    }
+}
+
+pub fn transform_tile(n: usize, pixel_data: TilePixels, transform: Transform) -> TilePixels {
+    let mut output_data = if transform.mirror { flip_lr_tile(n, pixel_data) } else { pixel_data };
+    for _ in 0..transform.rot {
+        output_data = rot_tile(n, output_data);
+    }
+    output_data
+}
+
+// the tile followed by each distinct extra copy the symmetry asks for.
+// copies that come out pixel identical to an earlier one are dropped so they dont pick up extra weight
+pub fn symmetry_variants(n: usize, px: TilePixels, symmetry: Symmetry) -> Vec<(&'static str, Transform, TilePixels)> {
+    let mut variants: Vec<(&'static str, Transform, TilePixels)> = Vec::new();
+    for (label, transform) in symmetry_transforms(symmetry) {
+        let candidate = transform_tile(n, px, transform);
+        if !variants.iter().any(|(_, _, v)| v[..n*n] == candidate[..n*n]) {
+            variants.push((label, transform, candidate));
+        }
+    }
+    variants
+}
+
+#[test]
+fn test_rot_tile() {
+    for n in 2..=MAX_TILE_SIZE {
+        let mut px = [(0, 0, 0); MAX_TILE_PX];
+        for (i, p) in px.iter_mut().take(n*n).enumerate() {
+            *p = (i as u8, 0, 0);
+        }
+        let rot = rot_tile(n, px);
+        // top left goes to top right
+        assert_eq!(rot[n-1], px[0]);
+        let back = rot_tile(n, rot_tile(n, rot_tile(n, rot)));
+        assert_eq!(back, px);
+    }
+}
+
+#[test]
+fn test_symmetry_variants() {
+    let n = 3;
+    let mut asymmetric = [(0, 0, 0); MAX_TILE_PX];
+    for (i, p) in asymmetric.iter_mut().take(n*n).enumerate() {
+        *p = (i as u8, 0, 0);
+    }
+    assert_eq!(symmetry_variants(n, asymmetric, Symmetry::None).len(), 1);
+    assert_eq!(symmetry_variants(n, asymmetric, Symmetry::Rotate).len(), 4);
+    assert_eq!(symmetry_variants(n, asymmetric, Symmetry::FlipLR).len(), 2);
+    assert_eq!(symmetry_variants(n, asymmetric, Symmetry::FlipBoth).len(), 4);
+    assert_eq!(symmetry_variants(n, asymmetric, Symmetry::All).len(), 8);
+
+    // a vertical stripe down the middle is its own left right mirror and its own 180
+    let mut stripe = [(0, 0, 0); MAX_TILE_PX];
+    for y in 0..n {
+        stripe[y*n + 1] = (255, 255, 255);
+    }
+    assert_eq!(symmetry_variants(n, stripe, Symmetry::FlipLR).len(), 1);
+    assert_eq!(symmetry_variants(n, stripe, Symmetry::FlipUD).len(), 1);
+    assert_eq!(symmetry_variants(n, stripe, Symmetry::Rotate).len(), 2);
+    assert_eq!(symmetry_variants(n, stripe, Symmetry::All).len(), 2);
+
+    let flat = [(9, 9, 9); MAX_TILE_PX];
+    assert_eq!(symmetry_variants(n, flat, Symmetry::All).len(), 1);
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use crate::krand::*;
+use crate::compat::*;
+use crate::greedy::*;
+use crate::local::*;
+use crate::tileset::*;
+
+#[derive(Clone, Debug)]
+pub struct TabuParams {
+    pub iterations: usize,
+    // how many iterations a tile that was just taken out of a cell can't go back in
+    pub tenure: usize,
+    // give up on the current patch of the search after this long without beating the best map
+    pub restart_after: usize,
+    // and go back to the best map with this fraction of its cells redrawn at random
+    pub perturb: f32,
+    // conflicted cells looked at per iteration, picked at random. Looking at all of them is
+    // too slow while the map is still mostly wrong
+    pub sample: usize,
+}
+
+impl Default for TabuParams {
+    fn default() -> TabuParams {
+        TabuParams {
+            iterations: 200_000,
+            tenure: 20,
+            restart_after: 2000,
+            perturb: 0.05,
+            sample: 16,
+        }
+    }
+}
+
+// tabu search over single cell changes. Every iteration makes the best change to any cell with a
+// bad border, even if that makes things worse, so it walks out of local minima rather than sitting
+// in them. Putting a tile back where it was just taken out is tabu for a while so it can't walk
+// straight back in, unless doing so would beat the best map so far (aspiration). When that stops
+// getting anywhere it restarts from the best map with some of it scrambled (diversification)
+pub fn tabu_tiling_with(tileset: &TileSet, w: usize, h: usize, seed: u32, params: &TabuParams, budget: &Budget) -> Vec<TileHandle> {
+    let n_tiles = tileset.vec.len();
+    let all = TileBits::full(n_tiles);
+
+    let mut tiling = random_fill(tileset, w, h, seed);
+    let mut conflicts = ConflictSet::new(tileset, &tiling, w, h);
+    let mut violations = count_violations(tileset, &tiling, w, h);
+    let mut best = tiling.clone();
+    let mut best_violations = violations;
+    let mut last_improvement = 0;
+    let mut restarts = 0;
+    // (cell, tile) -> the iteration it stops being tabu
+    let mut tabu: HashMap<(usize, TileHandle), usize> = HashMap::new();
+
+    let mut iteration = 0;
+    let deadline = budget.deadline();
+    while iteration < params.iterations && best_violations > 0 && budget.stop(iteration, deadline).is_none() {
+        if iteration - last_improvement > params.restart_after {
+            tiling.copy_from_slice(&best);
+            let n_perturb = ((w*h) as f32 * params.perturb).ceil() as usize;
+            for k in 0..n_perturb {
+                let r = khash(seed ^ khash(iteration as u32 ^ khash(k as u32)));
+                let cell = khash(r) as usize % (w*h);
+                tiling[cell] = tileset.pick_weighted(&all, r).unwrap();
+            }
+            conflicts = ConflictSet::new(tileset, &tiling, w, h);
+            violations = count_violations(tileset, &tiling, w, h);
+            tabu.clear();
+            last_improvement = iteration;
+            restarts += 1;
+        }
+
+        // best move over a sample of the conflicted cells, ties broken by a hash of the move
+        let mut best_move: Option<(i64, u32, usize, TileHandle)> = None;
+        let n_sample = conflicts.len().min(params.sample);
+        for k in 0..n_sample {
+            let cell = if n_sample == conflicts.len() {
+                conflicts.get(k)
+            } else {
+                conflicts.get(khash(seed ^ khash(iteration as u32) ^ khash(!(k as u32))) as usize % conflicts.len())
+            };
+            let old = tiling[cell];
+            let before = cell_violations(tileset, &tiling, w, h, cell, old) as i64;
+            for handle in (0..n_tiles as TileHandle).filter(|&t| t != old) {
+                let delta = cell_violations(tileset, &tiling, w, h, cell, handle) as i64 - before;
+                let is_tabu = tabu.get(&(cell, handle)).is_some_and(|&until| until > iteration);
+                let aspires = (violations as i64 + delta) < best_violations as i64;
+                if is_tabu && !aspires {
+                    continue;
+                }
+                let tie = khash(seed ^ khash(iteration as u32) ^ khash((cell * n_tiles) as u32 + handle));
+                if best_move.is_none_or(|(d, t, _, _)| (delta, tie) < (d, t)) {
+                    best_move = Some((delta, tie, cell, handle));
+                }
+            }
+        }
+        iteration += 1;
+
+        let Some((delta, _, cell, handle)) = best_move else {
+            // everything's tabu, wait it out
+            continue;
+        };
+        tabu.insert((cell, tiling[cell]), iteration + params.tenure);
+        tiling[cell] = handle;
+        conflicts.update_around(tileset, &tiling, w, h, cell);
+        violations = (violations as i64 + delta) as usize;
+        if violations < best_violations {
+            best_violations = violations;
+            best.copy_from_slice(&tiling);
+            last_improvement = iteration;
+        }
+        if tabu.len() > 64 * params.tenure.max(1) {
+            tabu.retain(|_, &mut until| until > iteration);
+        }
+    }
+
+    println!("tabu -- iterations: {} restarts: {} violations: {}", iteration, restarts, best_violations);
+    best
+}
+
+#[test]
+fn test_tabu() {
+    let tileset = load_tileset("test_tilesets/roads.png", 3).unwrap();
+    let (w, h) = (12, 10);
+    let tiling = tabu_tiling_with(&tileset, w, h, 69, &TabuParams { iterations: 20000, ..TabuParams::default() }, &Budget::default());
+    assert_eq!(count_violations(&tileset, &tiling, w, h), 0);
+
+    // restarting all the time still only ever hands back the best map it found
+    let params = TabuParams { iterations: 50, restart_after: 5, perturb: 0.5, ..TabuParams::default() };
+    let tiling = tabu_tiling_with(&tileset, w, h, 70, &params, &Budget::default());
+    assert!(count_violations(&tileset, &tiling, w, h) <= count_violations(&tileset, &random_fill(&tileset, w, h, 70), w, h));
+}
@@ -0,0 +1,656 @@
+use crate::krand::*;
+use crate::colour::*;
+use crate::image_output::*;
+use crate::constraint::*;
+use crate::grid::*;
+use crate::symmetry::*;
+use crate::manifest::*;
+use crate::rollback::*;
+use crate::rules::*;
+use crate::compat::*;
+
+#[derive(Copy, Clone)]
+pub struct TileSpec {
+    pub constraint: TileConstraint,
+    pub px_colour: [(u8, u8, u8); MAX_TILE_PX], // tile_size x tile_size row major
+
+    pub weight: f32,
+}
+
+// the non Copy stuff about a tile, kept out of TileSpec so the solver can keep copying those around
+#[derive(Clone, Debug)]
+pub struct TileMeta {
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum TilesetError {
+    Image(ImageError),
+    Manifest(ManifestError),
+    Rules(RulesError),
+    TileSize(usize),
+    // a sockets tileset with a tile that doesn't say what its sockets are
+    MissingSockets { x: usize, y: usize },
+    // x' on a tile when x isn't in the manifest's asymmetric list
+    UndeclaredAsymmetric { x: usize, y: usize, socket: String },
+    // the sheet has to be a whole number of (tile_size+1) cells
+    SheetSize { w: usize, h: usize, tile_size: usize },
+}
+
+impl std::fmt::Display for TilesetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TilesetError::Image(e) => write!(f, "{}", e),
+            TilesetError::Manifest(e) => write!(f, "{}", e),
+            TilesetError::Rules(e) => write!(f, "{}", e),
+            TilesetError::TileSize(n) => write!(f, "tile size {} isn't in 2..={}", n, MAX_TILE_SIZE),
+            TilesetError::MissingSockets { x, y } => write!(f, "tile ({}, {}) has no sockets in the manifest", x, y),
+            TilesetError::UndeclaredAsymmetric { x, y, socket } =>
+                write!(f, "tile ({}, {}) uses {}' but {} isn't listed as asymmetric", x, y, socket, socket),
+            TilesetError::SheetSize { w, h, tile_size } =>
+                write!(f, "{}x{} sheet isn't a whole number of {}x{} cells for tile size {}", w, h, tile_size+1, tile_size+1, tile_size),
+        }
+    }
+}
+
+impl std::error::Error for TilesetError {}
+
+impl From<ImageError> for TilesetError {
+    fn from(e: ImageError) -> TilesetError {
+        TilesetError::Image(e)
+    }
+}
+
+impl From<ManifestError> for TilesetError {
+    fn from(e: ManifestError) -> TilesetError {
+        TilesetError::Manifest(e)
+    }
+}
+
+impl From<RulesError> for TilesetError {
+    fn from(e: RulesError) -> TilesetError {
+        TilesetError::Rules(e)
+    }
+}
+
+pub type TileHandle = u32;
+pub const HANDLE_FAILED_PLACEMENT: u32 = u32::MAX; // mmm C style, yucky?
+pub const HANDLE_UNSET: u32 = u32::MAX - 1; // mmm C style, yucky?
+
+pub struct TileSet {
+    pub tile_size: usize,
+    pub vec: Vec<TileSpec>,
+    pub meta: Vec<TileMeta>, // parallel to vec
+    pub socket_names: Vec<String>, // indexed by Socket::id, empty for a pixels tileset
+    pub rules: Option<AdjacencyRules>,
+    pub index: CompatIndex,
+    // what the greedy solver does by default, from the manifest
+    pub rollback: RollbackPolicy,
+}
+
+// the tiles placed on each side of cell (i, j), indexed by Dir::idx, HANDLE_UNSET off the edge
+pub fn neighbour_handles(grid: &Grid<TileHandle>, i: usize, j: usize) -> [TileHandle; 4] {
+    let mut neighbours = [HANDLE_UNSET; 4];
+    for dir in DIRS {
+        if grid.neighbour(i, j, dir).is_some() {
+            let (ni, nj) = idx_in_dir(i, j, dir);
+            neighbours[dir.idx()] = grid.get(ni, nj);
+        }
+    }
+    neighbours
+}
+
+impl TileSet {
+
+    pub fn new(tile_size: usize) -> TileSet {
+        TileSet {
+            tile_size,
+            vec: Vec::new(),
+            meta: Vec::new(),
+            socket_names: Vec::new(),
+            rules: None,
+            index: CompatIndex::empty(),
+            rollback: RollbackPolicy::default(),
+        }
+    }
+
+    fn uses_sockets(&self) -> bool {
+        matches!(self.vec.first(), Some(TileSpec { constraint: TileConstraint::Sockets(_), .. }))
+    }
+
+    // the starting state of a cell, nothing placed next to it yet
+    pub fn unconstrained(&self) -> TileConstraint {
+        if self.uses_sockets() {
+            TileConstraint::Sockets(SocketConstraint { sides: [None; 4] })
+        } else {
+            TileConstraint::Pixels(constraint_unconstrained(self.tile_size))
+        }
+    }
+
+    // human readable version of one side of a tile
+    pub fn side_label(&self, handle: TileHandle, dir: Dir) -> String {
+        match self.vec[handle as usize].constraint {
+            TileConstraint::Pixels(c) => format!("{:?}", constraint_side(c, dir)),
+            TileConstraint::Sockets(c) => match c.sides[dir.idx()] {
+                Some(s) => format!("{}{}", self.socket_names[s.id as usize], if s.flipped { "'" } else { "" }),
+                None => "none".to_string(),
+            },
+        }
+    }
+
+    pub fn get_tilespec(&self, handle: TileHandle) -> TileSpec {
+        if handle == HANDLE_FAILED_PLACEMENT {
+            let b = (0, 0, 0);
+            return TileSpec {
+                constraint: self.unconstrained(),
+                px_colour: [b; MAX_TILE_PX],
+                weight: 0.0,
+            };
+        }
+        if handle == HANDLE_UNSET {
+            let m = (255, 0, 255);
+            return TileSpec {
+                constraint: self.unconstrained(),
+                px_colour: [m; MAX_TILE_PX],
+                weight: 0.0,
+            };
+        }
+
+        self.vec[handle as usize]
+    }
+
+    // neighbours are what's placed around the cell, indexed by Dir::idx
+    pub fn candidates(&self, neighbours: &[TileHandle; 4]) -> TileBits {
+        assert_eq!(self.index.n_tiles, self.vec.len(), "tiles changed since the last build_index");
+        self.index.candidates(neighbours)
+    }
+
+    // one of candidates, more likely the heavier it is
+    pub fn pick_weighted(&self, candidates: &TileBits, seed: u32) -> Option<TileHandle> {
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let weight_sum = candidates.iter().fold(0.0, |acc, handle| acc + self.vec[handle as usize].weight);
+        let choice = uniform_f32(seed) * weight_sum;
+        let mut acc = 0.0;
+        for handle in candidates.iter() {
+            acc += self.vec[handle as usize].weight;
+            if acc >= choice {
+                return Some(handle);
+            }
+        }
+        panic!("unreachable");
+    }
+
+    // has to be redone whenever tiles or rules change, the solver only looks at the index
+    pub fn build_index(&mut self) {
+        self.index = CompatIndex::new(self);
+    }
+
+    pub fn set_rules(&mut self, rules: Option<AdjacencyRules>) {
+        self.rules = rules;
+        self.build_index();
+    }
+
+    // tile names, a trailing * matches any name starting with the rest and #tag any tile tagged tag
+    pub fn tiles_matching(&self, pattern: &str) -> Vec<TileHandle> {
+        self.meta.iter().enumerate()
+            .filter(|(_, m)| match (pattern.strip_prefix('#'), pattern.strip_suffix('*')) {
+                (Some(tag), _) => m.tags.iter().any(|t| t == tag),
+                (None, Some(prefix)) => m.name.starts_with(prefix),
+                (None, None) => m.name == pattern,
+            })
+            .map(|(h, _)| h as TileHandle)
+            .collect()
+    }
+
+    // per run tweaks on top of whatever the tileset says. Gives back the names that didn't match anything
+    pub fn apply_weight_multipliers<'a>(&mut self, multipliers: &[(&'a str, f32)]) -> Vec<&'a str> {
+        let mut unmatched = Vec::new();
+        for &(pattern, multiplier) in multipliers {
+            let handles = self.tiles_matching(pattern);
+            if handles.is_empty() {
+                unmatched.push(pattern);
+            }
+            for handle in handles {
+                self.vec[handle as usize].weight *= multiplier;
+            }
+        }
+        unmatched
+    }
+
+    // how likely each tile is to be picked from the whole set, the odds against only the
+    // tiles that fit a particular cell are these renormalised
+    pub fn probabilities(&self) -> Vec<f32> {
+        let total: f32 = self.vec.iter().map(|t| t.weight).sum();
+        self.vec.iter().map(|t| if total > 0.0 { t.weight / total } else { 0.0 }).collect()
+    }
+
+    pub fn print_weight_report(&self) {
+        for ((meta, spec), p) in self.meta.iter().zip(self.vec.iter()).zip(self.probabilities()) {
+            println!("{:>24} weight {:>8.4} p {:>6.2}%", meta.name, spec.weight, p * 100.0);
+        }
+    }
+
+    // make colours that should count as the same actually the same in the constraints, px_colour
+    // keeps the real ones for drawing. See colour_representatives
+    pub fn merge_border_colours(&mut self, classes: &[Vec<(u8, u8, u8)>], tolerance: f32) {
+        let mut colours: Vec<(u8, u8, u8)> = Vec::new();
+        for spec in self.vec.iter() {
+            if let TileConstraint::Pixels(c) = spec.constraint {
+                for (i, &px) in c.px[..c.n*c.n].iter().enumerate() {
+                    if c.mask & (1 << i) == 0 && !colours.contains(&px) {
+                        colours.push(px);
+                    }
+                }
+            }
+        }
+        let representatives = colour_representatives(&colours, classes, tolerance);
+        for spec in self.vec.iter_mut() {
+            if let TileConstraint::Pixels(c) = &mut spec.constraint {
+                for px in c.px[..c.n*c.n].iter_mut() {
+                    if let Some(idx) = colours.iter().position(|c| c == px) {
+                        *px = representatives[idx];
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn push(&mut self, spec: TileSpec, meta: TileMeta) {
+        self.vec.push(spec);
+        self.meta.push(meta);
+    }
+
+    // None for a primed socket that isn't declared asymmetric
+    fn socket(&mut self, label: &str, manifest: &Manifest) -> Option<Socket> {
+        let (base, flipped) = match label.strip_suffix('\'') {
+            Some(base) => (base, true),
+            None => (label, false),
+        };
+        let symmetric = !manifest.asymmetric_socket(base);
+        if flipped && symmetric {
+            return None;
+        }
+        let id = match self.socket_names.iter().position(|name| name == base) {
+            Some(id) => id,
+            None => {
+                self.socket_names.push(base.to_string());
+                self.socket_names.len() - 1
+            },
+        };
+        Some(Socket {
+            id: id as u16,
+            flipped,
+            symmetric,
+        })
+    }
+}
+
+// the weight control pixel is read as its brightness, white is 1 and black is 0
+pub fn weight_from_px(px: (u8, u8, u8)) -> f32 {
+    (px.0 as f32 + px.1 as f32 + px.2 as f32) / (255.0*3.0)
+}
+
+// each tile is a (tile_size+1) square cell: tile_size x tile_size of pixels plus a control row and column.
+// control pixels: red at (n,n) comments out, (n,0) picks the symmetry copies to add, (0,n) is the weight.
+// magenta or fully transparent tile pixels are wildcards, on the border they'll sit against anything.
+// the manifest, if there is one, overrides all of those, and can switch the tileset over to sockets
+pub fn make_tileset(image: &ImageBuffer, tile_size: usize, manifest: Option<&Manifest>) -> Result<TileSet, TilesetError> {
+    if !(2..=MAX_TILE_SIZE).contains(&tile_size) {
+        return Err(TilesetError::TileSize(tile_size));
+    }
+    let n = tile_size;
+    let cell = n + 1;
+    if !image.w.is_multiple_of(cell) || !image.h.is_multiple_of(cell) {
+        return Err(TilesetError::SheetSize { w: image.w, h: image.h, tile_size });
+    }
+    let n_tiles_x = image.w / cell;
+    let n_tiles_y = image.h / cell;
+
+    let use_sockets = manifest.and_then(|m| m.backend) == Some(Backend::Sockets);
+
+    let mut t = TileSet::new(tile_size);
+
+    for tile_i in 0..n_tiles_x {
+        for tile_j in 0..n_tiles_y {
+            let tile_px = |tx: usize, ty: usize| image.get_px(tile_i*cell + tx, tile_j*cell + ty);
+            let entry = manifest.and_then(|m| m.entry(tile_i, tile_j));
+
+            let comment = tile_px(n, n) == (0xFF, 0x00, 0x00);
+            let enabled = entry.and_then(|e| e.enabled).unwrap_or(!comment);
+            let symmetry = entry.and_then(|e| e.symmetry)
+                .map(|s| s.symmetry())
+                .unwrap_or_else(|| Symmetry::from_control_px(tile_px(n, 0)));
+
+            if enabled {
+                let weight = entry.and_then(|e| e.weight)
+                    .unwrap_or_else(|| weight_from_px(tile_px(0, n)));
+                let name = entry.and_then(|e| e.name.clone())
+                    .unwrap_or_else(|| format!("tile_{}_{}", tile_i, tile_j));
+                let tags = entry.map(|e| e.tags.clone()).unwrap_or_default();
+
+                let mut px_colour = [(0, 0, 0); MAX_TILE_PX];
+                for ty in 0..n {
+                    for tx in 0..n {
+                        let (x, y) = (tile_i*cell + tx, tile_j*cell + ty);
+                        px_colour[ty*n + tx] = if image.alpha[y*image.w + x] == 0 { WILDCARD } else { tile_px(tx, ty) };
+                    }
+                }
+                let meta = |label: &str| TileMeta {
+                    name: if label.is_empty() { name.clone() } else { format!("{}_{}", name, label) },
+                    tags: tags.clone(),
+                };
+
+                if use_sockets {
+                    // sockets don't look at the pixels, wildcards are only there for looks
+                    fill_wildcards(n, &mut px_colour);
+                    let labels = entry.and_then(|e| e.sockets.as_ref())
+                        .ok_or(TilesetError::MissingSockets { x: tile_i, y: tile_j })?;
+                    let manifest = manifest.unwrap();
+                    let mut sides = [None; 4];
+                    for (side, label) in sides.iter_mut().zip(labels.iter()) {
+                        let socket = t.socket(label, manifest).ok_or_else(|| TilesetError::UndeclaredAsymmetric {
+                            x: tile_i,
+                            y: tile_j,
+                            socket: label.trim_end_matches('\'').to_string(),
+                        })?;
+                        *side = Some(socket);
+                    }
+                    let sockets = SocketConstraint { sides };
+
+                    // same as symmetry_variants but a copy only counts as a duplicate if the sockets match too
+                    let mut variants: Vec<(TilePixels, SocketConstraint)> = Vec::new();
+                    for (label, transform) in symmetry_transforms(symmetry) {
+                        let variant = transform_tile(n, px_colour, transform);
+                        let variant_sockets = socket_constraint_transform(sockets, transform.mirror, transform.rot);
+                        if variants.iter().any(|(v, vs)| v[..n*n] == variant[..n*n] && *vs == variant_sockets) {
+                            continue;
+                        }
+                        variants.push((variant, variant_sockets));
+                        t.push(TileSpec {
+                            px_colour: variant,
+                            weight,
+                            constraint: TileConstraint::Sockets(variant_sockets),
+                        }, meta(label));
+                    }
+                } else {
+                    for (label, _, variant) in symmetry_variants(n, px_colour, symmetry) {
+                        let constraint = TileConstraint::Pixels(constraint_from_px_colour(n, &variant));
+                        let mut drawn = variant;
+                        fill_wildcards(n, &mut drawn);
+                        t.push(TileSpec {
+                            px_colour: drawn,
+                            weight,
+                            constraint,
+                        }, meta(label));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(rollback) = manifest.and_then(|m| m.rollback.clone()) {
+        t.rollback = rollback;
+    }
+    if let Some(m) = manifest.filter(|_| !use_sockets) {
+        if !m.colour_classes.is_empty() || m.colour_tolerance.is_some() {
+            let classes: Vec<Vec<(u8, u8, u8)>> = m.colour_classes.iter()
+                .map(|class| class.iter().map(|c| c.0).collect())
+                .collect();
+            t.merge_border_colours(&classes, m.colour_tolerance.unwrap_or(0.0));
+        }
+    }
+
+    t.build_index();
+    Ok(t)
+}
+
+// loads the png and its manifest if it has one. tile_size is used unless the manifest says otherwise
+pub fn load_tileset(path: &str, tile_size: usize) -> Result<TileSet, TilesetError> {
+    let imgbuf = ImageBuffer::new_from_file(path)?;
+    let manifest = Manifest::load_sidecar(path)?;
+    let tile_size = manifest.as_ref().and_then(|m| m.tile_size).unwrap_or(tile_size);
+    let mut tileset = make_tileset(&imgbuf, tile_size, manifest.as_ref())?;
+
+    if let Some(manifest) = &manifest {
+        let cell = tile_size + 1;
+        for entry in manifest.unmatched_entries(imgbuf.w / cell, imgbuf.h / cell) {
+            println!("{}: manifest entry for tile ({}, {}) doesn't match any tile", path, entry.x, entry.y);
+        }
+    }
+
+    let rules = AdjacencyRules::load_sidecar(path, &tileset)?;
+    if rules.is_some() {
+        tileset.set_rules(rules);
+    }
+    Ok(tileset)
+}
+
+#[test]
+fn test_any_tile_size() {
+    for n in [3, 5, 6, 8] {
+        // two tiles: solid green and green with a blue interior
+        let mut image = ImageBuffer::new(2*(n+1), n+1);
+        for tile in 0..2 {
+            for y in 0..n {
+                for x in 0..n {
+                    let interior = x > 0 && y > 0 && x < n-1 && y < n-1;
+                    let colour = if interior && tile == 1 { (0, 0, 255) } else { (0, 255, 0) };
+                    image.set_px(tile*(n+1) + x, y, colour);
+                }
+            }
+            image.set_px(tile*(n+1), n, (1, 0, 0));
+        }
+        let tileset = make_tileset(&image, n, None).unwrap();
+        assert_eq!(tileset.vec.len(), 2);
+        let tiling = crate::greedy_run(&tileset, 7, 5, 69, &crate::GreedyParams::default()).tiles;
+        assert_eq!(tiling.len(), 35);
+        assert!(tiling.iter().all(|&h| h == 0 || h == 1));
+    }
+}
+
+#[test]
+fn test_manifest_overrides_control_pixels() {
+    let n = 3;
+    let mut image = ImageBuffer::new(2*(n+1), n+1);
+    // second tile is commented out and asks for rotations
+    image.set_px(2*(n+1) - 1, n, (0xFF, 0, 0));
+    image.set_px(2*(n+1) - 1, 0, (0, 0, 0xFF));
+    image.set_px(n+1, 0, (0, 0xFF, 0));
+
+    let manifest = Manifest::parse(r#"
+        [[tile]]
+        x = 0
+        y = 0
+        name = "grass"
+        weight = 0.5
+        tags = ["ground"]
+
+        [[tile]]
+        x = 1
+        y = 0
+        name = "corner"
+        enabled = true
+        symmetry = "flip_lr"
+    "#).unwrap();
+    let tileset = make_tileset(&image, n, Some(&manifest)).unwrap();
+    let names: Vec<&str> = tileset.meta.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["grass", "corner", "corner_flip_lr"]);
+    assert_eq!(tileset.vec[0].weight, 0.5);
+    assert_eq!(tileset.meta[0].tags, ["ground"]);
+    assert_eq!(tileset.tiles_matching("#ground"), [0]);
+}
+
+#[test]
+fn test_load_tileset_errors() {
+    assert!(matches!(load_tileset("test_tilesets/nope.png", 3), Err(TilesetError::Image(ImageError::Io(_)))));
+    // the bundled sheets are 20x20, 5 cells of 4
+    assert!(load_tileset("test_tilesets/roads.png", 3).is_ok());
+    assert!(matches!(load_tileset("test_tilesets/roads.png", 5), Err(TilesetError::SheetSize { w: 20, h: 20, tile_size: 5 })));
+    assert!(matches!(load_tileset("test_tilesets/roads.png", 9), Err(TilesetError::TileSize(9))));
+    assert!(matches!(make_tileset(&ImageBuffer::new(20, 20), 1, None), Err(TilesetError::TileSize(1))));
+    assert!(matches!(make_tileset(&ImageBuffer::new(20, 18), 3, None), Err(TilesetError::SheetSize { w: 20, h: 18, tile_size: 3 })));
+    let example = ImageBuffer::new(8, 8);
+    assert!(matches!(crate::overlap::tileset_from_example(&example, 9, crate::symmetry::Symmetry::None, true), Err(TilesetError::TileSize(9))));
+}
+
+#[test]
+fn test_socket_tileset() {
+    let n = 3;
+    let image = ImageBuffer::new(3*(n+1), n+1);
+    // a shore runs north to south with land on its west, so it's asymmetric and
+    // a mirrored shore can't sit on the end of a normal one
+    let manifest = Manifest::parse(r#"
+        backend = "sockets"
+        asymmetric = ["shore"]
+
+        [[tile]]
+        x = 0
+        y = 0
+        name = "land"
+        sockets = ["land", "land", "land", "land"]
+
+        [[tile]]
+        x = 1
+        y = 0
+        name = "shore"
+        symmetry = "all"
+        sockets = ["shore", "sea", "shore'", "land"]
+
+        [[tile]]
+        x = 2
+        y = 0
+        name = "sea"
+        sockets = ["sea", "sea", "sea", "sea"]
+    "#).unwrap();
+    let tileset = make_tileset(&image, n, Some(&manifest)).unwrap();
+    assert_eq!(tileset.socket_names, ["land", "shore", "sea"]);
+    // land, sea and the shore in 4 orientations. Mirrored it's the same as turned round twice
+    assert_eq!(tileset.vec.len(), 6);
+    assert_eq!(tileset.side_label(1, Dir::South), "shore'");
+
+    let (w, h) = (12, 9);
+    let tiling = crate::greedy_run(&tileset, w, h, 69, &crate::GreedyParams::default()).tiles;
+    assert!(tiling.iter().all(|&t| t != HANDLE_FAILED_PLACEMENT));
+    for j in 0..h {
+        for i in 0..w {
+            let a = tileset.get_tilespec(tiling[j*w + i]);
+            if i + 1 < w {
+                let b = tileset.get_tilespec(tiling[j*w + i + 1]);
+                assert!(tile_constraint_fits(a.constraint, b.constraint, Dir::East));
+            }
+            if j + 1 < h {
+                let b = tileset.get_tilespec(tiling[(j+1)*w + i]);
+                assert!(tile_constraint_fits(a.constraint, b.constraint, Dir::South));
+            }
+        }
+    }
+
+    let no_sockets = Manifest::parse("backend = \"sockets\"").unwrap();
+    assert!(matches!(make_tileset(&image, n, Some(&no_sockets)), Err(TilesetError::MissingSockets { x: 0, y: 0 })));
+
+    // symmetric sockets all round and plain pixels both come out as the one tile whatever the symmetry
+    let image = ImageBuffer::new(n+1, n+1);
+    let symmetric = Manifest::parse(r#"
+        backend = "sockets"
+
+        [[tile]]
+        x = 0
+        y = 0
+        symmetry = "all"
+        sockets = ["land", "land", "land", "land"]
+    "#).unwrap();
+    let pixels = Manifest::parse("[[tile]]\nx = 0\ny = 0\nsymmetry = \"all\"").unwrap();
+    let by_sockets = make_tileset(&image, n, Some(&symmetric)).unwrap().vec.len();
+    let by_pixels = make_tileset(&image, n, Some(&pixels)).unwrap().vec.len();
+    assert_eq!((by_sockets, by_pixels), (1, 1));
+
+    // a primed socket has to be declared
+    let undeclared = Manifest::parse(r#"
+        backend = "sockets"
+
+        [[tile]]
+        x = 0
+        y = 0
+        sockets = ["land", "shore'", "land", "land"]
+    "#).unwrap();
+    assert!(matches!(make_tileset(&image, n, Some(&undeclared)),
+        Err(TilesetError::UndeclaredAsymmetric { x: 0, y: 0, socket }) if socket == "shore"));
+}
+
+#[test]
+fn test_weights() {
+    assert_eq!(weight_from_px((255, 255, 255)), 1.0);
+    assert_eq!(weight_from_px((0, 0, 0)), 0.0);
+    assert!((weight_from_px((51, 51, 51)) - 0.2).abs() < 1e-6);
+
+    let n = 3;
+    let mut image = ImageBuffer::new(2*(n+1), n+1);
+    image.set_px(0, n, (255, 255, 255));
+    image.set_px(n+1, n, (85, 85, 85));
+    let manifest = Manifest::parse("[[tile]]\nx = 0\ny = 0\nname = \"a\"\n[[tile]]\nx = 1\ny = 0\nname = \"b\"").unwrap();
+    let mut tileset = make_tileset(&image, n, Some(&manifest)).unwrap();
+    let p = tileset.probabilities();
+    assert!((p[0] - 0.75).abs() < 1e-6 && (p[1] - 0.25).abs() < 1e-6);
+
+    let unmatched = tileset.apply_weight_multipliers(&[("b*", 3.0), ("c", 2.0)]);
+    assert_eq!(unmatched, ["c"]);
+    let p = tileset.probabilities();
+    assert!((p[0] - 0.5).abs() < 1e-6 && (p[1] - 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn test_colour_classes() {
+    // two greens one unit apart, and a blue
+    let n = 3;
+    let colours = [(0x37, 0xe0, 0x27), (0x38, 0xe0, 0x27), (0, 0, 255)];
+    let mut image = ImageBuffer::new(3*(n+1), n+1);
+    for (tile, &colour) in colours.iter().enumerate() {
+        for y in 0..n {
+            for x in 0..n {
+                image.set_px(tile*(n+1) + x, y, colour);
+            }
+        }
+    }
+    let fits = |t: &TileSet, a: usize, b: usize| tile_constraint_fits(t.vec[a].constraint, t.vec[b].constraint, Dir::East);
+
+    let plain = make_tileset(&image, n, None).unwrap();
+    assert!(!fits(&plain, 0, 1));
+
+    let tolerant = make_tileset(&image, n, Some(&Manifest::parse("colour_tolerance = 2.3").unwrap())).unwrap();
+    assert!(fits(&tolerant, 0, 1) && fits(&tolerant, 1, 0));
+    assert!(!fits(&tolerant, 0, 2));
+    // still drawn the way they were painted
+    assert_eq!(tolerant.vec[1].px_colour[0], colours[1]);
+
+    let classes = Manifest::parse("colour_classes = [[\"#38e027\", \"#0000ff\"]]").unwrap();
+    let classed = make_tileset(&image, n, Some(&classes)).unwrap();
+    assert!(fits(&classed, 1, 2));
+    assert!(!fits(&classed, 0, 1));
+
+    assert!(matches!(Manifest::parse("colour_classes = [[\"green\"]]"), Err(ManifestError::Parse(_))));
+}
+
+#[test]
+fn test_wildcard_pixels() {
+    // grass with a see-through top middle pixel and a magenta bottom middle one
+    let n = 3;
+    let g = (0, 255, 0);
+    let mut image = ImageBuffer::new(n+1, n+1);
+    for y in 0..n {
+        for x in 0..n {
+            image.set_px(x, y, g);
+        }
+    }
+    image.alpha[1] = 0;
+    image.set_px(1, n-1, WILDCARD);
+    let tileset = make_tileset(&image, n, None).unwrap();
+    match tileset.vec[0].constraint {
+        TileConstraint::Pixels(c) => assert_eq!(c.mask & border_mask(n), (1 << 1) | (1 << 7)),
+        TileConstraint::Sockets(_) => panic!(),
+    }
+    assert_eq!(tileset.vec[0].px_colour[..n*n], [g; 9]);
+}